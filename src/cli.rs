@@ -0,0 +1,141 @@
+use std::path::PathBuf;
+
+use getopts::Options;
+use irc::client::data::Config as IrcConfig;
+
+use config::Config;
+use error;
+
+/// The command-line arguments of the alectro binary.
+#[derive(Clone, Debug, Default)]
+pub struct Args {
+    pub config: Option<PathBuf>,
+    /// A server to connect to instead of the configured ones.
+    pub server: Option<String>,
+    pub port: Option<u16>,
+    pub use_ssl: Option<bool>,
+    pub nickname: Option<String>,
+    /// Channels to join on every server, in addition to the configured ones.
+    pub channels: Vec<String>,
+    /// Validates the config and exits instead of connecting.
+    pub check_config: bool,
+    pub help: bool,
+}
+
+impl Args {
+    /// Parses the arguments, excluding the program name.
+    pub fn parse<I>(args: I) -> error::Result<Args> where I: IntoIterator<Item = String> {
+        let matches = options().parse(args).map_err(|e| {
+            error::Error::InvalidArgs { reason: e.to_string() }
+        })?;
+        if !matches.free.is_empty() {
+            return Err(error::Error::InvalidArgs {
+                reason: format!("unexpected argument `{}`", matches.free[0]),
+            });
+        }
+
+        let port = match matches.opt_str("port") {
+            Some(port) => Some(port.parse().map_err(|_| error::Error::InvalidArgs {
+                reason: format!("invalid port `{}`", port),
+            })?),
+            None => None,
+        };
+
+        let use_ssl = match (matches.opt_present("tls"), matches.opt_present("no-tls")) {
+            (true, true) => return Err(error::Error::InvalidArgs {
+                reason: "--tls and --no-tls cannot be used together".to_owned(),
+            }),
+            (true, false) => Some(true),
+            (false, true) => Some(false),
+            (false, false) => None,
+        };
+
+        Ok(Args {
+            config: matches.opt_str("config").map(PathBuf::from),
+            server: matches.opt_str("server"),
+            port: port,
+            use_ssl: use_ssl,
+            nickname: matches.opt_str("nick"),
+            channels: matches.opt_strs("join").iter().flat_map(|chans| {
+                chans.split(',').filter(|chan| !chan.is_empty()).map(|chan| chan.to_owned())
+            }).collect(),
+            check_config: matches.opt_present("check-config"),
+            help: matches.opt_present("help"),
+        })
+    }
+
+    pub fn usage(program: &str) -> String {
+        options().usage(&format!("Usage: {} [options]", program))
+    }
+
+    /// Loads the config file and applies the arguments to it. A missing config file at the
+    /// default location is only an error if no server was given, or if a config file is left at
+    /// the legacy location, which would otherwise be ignored without notice.
+    pub fn load_config(&self) -> error::Result<Config> {
+        let path = self.config.clone().or_else(Config::default_path);
+        if self.config.is_none() && !path.as_ref().is_some_and(|path| path.exists()) {
+            if let Some(legacy) = Config::legacy_path().filter(|legacy| legacy.exists()) {
+                return Err(error::Error::LegacyConfig {
+                    path: legacy.display().to_string(),
+                    new_path: path.map(|path| path.display().to_string()).unwrap_or_default(),
+                });
+            }
+        }
+        let mut config = match path {
+            Some(ref path) if self.config.is_some() || path.exists() || self.server.is_none() => {
+                Config::load(path)?
+            }
+            _ => Config::default(),
+        };
+
+        if let Some(ref server) = self.server {
+            // The new server keeps the identity of the first configured one, if any.
+            let base = config.servers.first().cloned().unwrap_or_default();
+            config.servers = vec![IrcConfig {
+                server: Some(server.clone()),
+                nickname: base.nickname,
+                alt_nicks: base.alt_nicks,
+                username: base.username,
+                realname: base.realname,
+                .. Default::default()
+            }];
+        }
+
+        for server in &mut config.servers {
+            if self.port.is_some() {
+                server.port = self.port;
+            }
+            if self.use_ssl.is_some() {
+                server.use_ssl = self.use_ssl;
+            }
+            if self.nickname.is_some() {
+                server.nickname = self.nickname.clone();
+            }
+            if !self.channels.is_empty() {
+                let channels = server.channels.get_or_insert_with(Vec::new);
+                for chan in &self.channels {
+                    if !channels.contains(chan) {
+                        channels.push(chan.clone());
+                    }
+                }
+            }
+        }
+
+        config.validate()?;
+        Ok(config)
+    }
+}
+
+fn options() -> Options {
+    let mut opts = Options::new();
+    opts.optopt("c", "config", "load the config from PATH", "PATH");
+    opts.optopt("s", "server", "connect to HOST instead of the configured servers", "HOST");
+    opts.optopt("p", "port", "connect on PORT", "PORT");
+    opts.optflag("", "tls", "connect using TLS");
+    opts.optflag("", "no-tls", "connect without TLS");
+    opts.optopt("n", "nick", "use NICK as the nickname", "NICK");
+    opts.optmulti("j", "join", "join the comma-separated CHANNELS", "CHANNELS");
+    opts.optflag("", "check-config", "check the config and exit");
+    opts.optflag("h", "help", "print this help and exit");
+    opts
+}
@@ -0,0 +1,407 @@
+use std::collections::{HashMap, HashSet};
+use std::env;
+use std::fs::File;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+
+use chrono::format::{Item, StrftimeItems};
+use irc::client::data::Config as IrcConfig;
+use regex::Regex;
+use termion::event::{Event, Key};
+use toml;
+
+use error;
+use model;
+use view::Theme;
+
+/// The configuration of alectro, along with each of the networks that it connects to.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    /// The networks to connect to, each given as a `[[servers]]` table.
+    pub servers: Vec<IrcConfig>,
+    pub ui: UiConfig,
+    pub logging: LogConfig,
+    pub highlights: HighlightConfig,
+    /// Keys mapped to the names of the actions that they perform.
+    #[serde(rename = "keybindings")]
+    key_names: HashMap<String, String>,
+    /// The default key bindings, overridden by those in the config.
+    #[serde(skip)]
+    pub keybindings: HashMap<Event, Action>,
+    pub colors: Theme,
+}
+
+impl Default for Config {
+    fn default() -> Config {
+        Config {
+            servers: Vec::new(),
+            ui: UiConfig::default(),
+            logging: LogConfig::default(),
+            highlights: HighlightConfig::default(),
+            key_names: HashMap::new(),
+            keybindings: default_key_bindings(),
+            colors: Theme::default(),
+        }
+    }
+}
+
+/// The default strftime format of chat timestamps.
+pub const DEFAULT_TIMESTAMP_FORMAT: &str = "%H:%M";
+
+/// The default width at which the names of tabs are cut off.
+pub const DEFAULT_TAB_NAME_WIDTH: usize = 20;
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct UiConfig {
+    /// The strftime format of chat timestamps, or empty to hide them.
+    pub timestamp_format: String,
+    pub nicklist_width: u16,
+    /// The width at which the names of buffers in the tab line are cut off.
+    pub tab_name_width: usize,
+    /// Whether each tab is prefixed with its number.
+    pub tab_numbers: bool,
+    /// The number of events kept in each buffer's history.
+    pub scrollback: usize,
+    /// The prompt shown before the input line, in which `{buffer}` is replaced by the name of the
+    /// current buffer and `{network}` by the name of its network.
+    pub prompt: String,
+    /// Messages of more than this many lines must be sent twice before they are sent to a
+    /// channel, so that large pastes are not sent by accident.
+    pub paste_confirm_lines: usize,
+    /// The number of sent lines kept in the input history, which is saved in the data directory.
+    pub history_size: usize,
+    /// Whether each buffer has its own input history, rather than sharing one.
+    pub history_per_buffer: bool,
+    /// Regular expressions matching sent lines that are left out of the saved input history.
+    pub history_exclude: Vec<String>,
+}
+
+impl Default for UiConfig {
+    fn default() -> UiConfig {
+        UiConfig {
+            timestamp_format: DEFAULT_TIMESTAMP_FORMAT.to_owned(),
+            nicklist_width: 20,
+            tab_name_width: DEFAULT_TAB_NAME_WIDTH,
+            tab_numbers: false,
+            scrollback: model::DEFAULT_CAPACITY,
+            prompt: "[{buffer}] ".to_owned(),
+            paste_confirm_lines: 5,
+            history_size: model::DEFAULT_HISTORY_SIZE,
+            history_per_buffer: false,
+            history_exclude: vec![
+                // Passwords sent to services, e.g. `/msg NickServ IDENTIFY <password>`.
+                r"(?i)^/(msg|query|quote privmsg) \S*serv\b".to_owned(),
+                r"(?i)^/quote (pass|oper|authenticate)\b".to_owned(),
+            ],
+        }
+    }
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct LogConfig {
+    /// Whether the events of each buffer are logged, under `logs` in the data directory.
+    pub enabled: bool,
+    /// Whether formatting codes (colors, bold, etc.) are removed from logged messages.
+    pub strip_formatting: bool,
+    /// The buffers not to log, each given by name (e.g. `#chan`) or as `network/name`.
+    pub exclude: Vec<String>,
+    /// The number of events read back from a buffer's log when it is opened, or 0 for none.
+    pub backlog: usize,
+}
+
+impl Default for LogConfig {
+    fn default() -> LogConfig {
+        LogConfig {
+            enabled: true,
+            strip_formatting: true,
+            exclude: vec![model::RAW.to_owned()],
+            backlog: 50,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct HighlightConfig {
+    /// Words that highlight the messages that contain them, like our nickname does.
+    pub words: Vec<String>,
+    /// Regular expressions that highlight the messages that they match.
+    pub patterns: Vec<String>,
+    /// Whether highlights are also collected in a buffer of each network.
+    pub buffer: bool,
+}
+
+impl Default for HighlightConfig {
+    fn default() -> HighlightConfig {
+        HighlightConfig {
+            words: Vec::new(),
+            patterns: Vec::new(),
+            buffer: true,
+        }
+    }
+}
+
+impl HighlightConfig {
+    /// Builds a highlighter from the words and patterns, skipping any invalid patterns, which
+    /// `Config::load` rejects.
+    pub fn highlighter(&self) -> model::Highlighter {
+        model::Highlighter::new(
+            self.words.clone(),
+            self.patterns.iter().filter_map(|pattern| Regex::new(pattern).ok()).collect()
+        )
+    }
+}
+
+/// An action that can be bound to a key.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Action {
+    ScrollUp,
+    ScrollDown,
+    PageUp,
+    PageDown,
+    ScrollToBottom,
+    ToggleNickList,
+    Quit,
+}
+
+impl Action {
+    fn from_name(name: &str) -> Option<Action> {
+        match name {
+            "scroll_up" => Some(Action::ScrollUp),
+            "scroll_down" => Some(Action::ScrollDown),
+            "page_up" => Some(Action::PageUp),
+            "page_down" => Some(Action::PageDown),
+            "scroll_to_bottom" => Some(Action::ScrollToBottom),
+            "toggle_nicklist" => Some(Action::ToggleNickList),
+            "quit" => Some(Action::Quit),
+            _ => None,
+        }
+    }
+}
+
+impl Config {
+    /// The default location of the config file, `$XDG_CONFIG_HOME/alectro/config.toml`.
+    pub fn default_path() -> Option<PathBuf> {
+        let config_home = match env::var_os("XDG_CONFIG_HOME") {
+            Some(ref dir) if !dir.is_empty() => Some(PathBuf::from(dir)),
+            _ => env::home_dir().map(|home| home.join(".config")),
+        };
+        config_home.map(|dir| dir.join("alectro").join("config.toml"))
+    }
+
+    /// The location of the config file in earlier versions, `~/.alectro.toml`. Such a file only
+    /// held the settings of a single server, and is no longer read.
+    pub fn legacy_path() -> Option<PathBuf> {
+        env::home_dir().map(|home| home.join(".alectro.toml"))
+    }
+
+    /// The directory in which alectro keeps its data, `$XDG_DATA_HOME/alectro`.
+    pub fn data_dir() -> Option<PathBuf> {
+        let data_home = match env::var_os("XDG_DATA_HOME") {
+            Some(ref dir) if !dir.is_empty() => Some(PathBuf::from(dir)),
+            _ => env::home_dir().map(|home| home.join(".local").join("share")),
+        };
+        data_home.map(|dir| dir.join("alectro"))
+    }
+
+    /// Loads the config file at the specified path, checking everything but the servers, which
+    /// may still be overridden on the command line.
+    pub fn load<P: AsRef<Path>>(path: P) -> error::Result<Config> {
+        let path = path.as_ref();
+        let invalid = |reason: String| error::Error::InvalidConfig {
+            path: path.display().to_string(),
+            reason: reason,
+        };
+
+        let mut data = String::new();
+        File::open(path).and_then(|mut file| file.read_to_string(&mut data)).map_err(|e| {
+            error::Error::ConfigRead { path: path.display().to_string(), cause: e }
+        })?;
+        let mut config: Config = toml::from_str(&data).map_err(|e| invalid(e.to_string()))?;
+
+        if StrftimeItems::new(&config.ui.timestamp_format).any(|item| item == Item::Error) {
+            return Err(invalid(format!(
+                "ui.timestamp_format `{}` is not a valid strftime format",
+                config.ui.timestamp_format
+            )));
+        }
+        if config.ui.scrollback == 0 {
+            return Err(invalid("ui.scrollback must be at least 1".to_owned()));
+        }
+        if config.ui.tab_name_width == 0 {
+            return Err(invalid("ui.tab_name_width must be at least 1".to_owned()));
+        }
+        for pattern in &config.highlights.patterns {
+            if let Err(e) = Regex::new(pattern) {
+                return Err(invalid(format!("invalid pattern in highlights.patterns: {}", e)));
+            }
+        }
+        for pattern in &config.ui.history_exclude {
+            if let Err(e) = Regex::new(pattern) {
+                return Err(invalid(format!("invalid pattern in ui.history_exclude: {}", e)));
+            }
+        }
+
+        config.keybindings = default_key_bindings();
+        for (key_name, action_name) in &config.key_names {
+            let key = parse_key(key_name).ok_or_else(|| {
+                invalid(format!("unknown key `{}` in [keybindings]", key_name))
+            })?;
+            // Keys can be unbound from their defaults by binding them to `none`.
+            if action_name == "none" {
+                config.keybindings.remove(&key);
+                continue;
+            }
+            let action = Action::from_name(action_name).ok_or_else(|| {
+                invalid(format!("unknown action `{}` in [keybindings]", action_name))
+            })?;
+            config.keybindings.insert(key, action);
+        }
+
+        Ok(config)
+    }
+
+    /// Checks that there is at least one server, and that each has an address and a nickname.
+    pub fn validate(&self) -> error::Result<()> {
+        if self.servers.is_empty() {
+            return Err(error::Error::NoServers);
+        }
+        for (i, server) in self.servers.iter().enumerate() {
+            if server.nickname.is_none() {
+                return Err(error::Error::IncompleteServer { index: i, option: "nickname" });
+            }
+            if server.server.is_none() {
+                return Err(error::Error::IncompleteServer { index: i, option: "server" });
+            }
+        }
+        // Networks share the buffers and logs of their name, so each name must be used once.
+        let mut names = HashSet::new();
+        for server in &self.servers {
+            let name = network_name(server);
+            if !names.insert(name.to_lowercase()) {
+                return Err(error::Error::DuplicateNetwork { name: name.to_owned() });
+            }
+        }
+        Ok(())
+    }
+}
+
+/// The name of a network, which is its `name` option, or its server's address otherwise.
+pub fn network_name(server: &IrcConfig) -> &str {
+    server.get_option("name").unwrap_or_else(|| server.server().unwrap())
+}
+
+fn default_key_bindings() -> HashMap<Event, Action> {
+    let defaults = [
+        ("Shift-Up", Action::ScrollUp),
+        ("Shift-Down", Action::ScrollDown),
+        ("PageUp", Action::PageUp),
+        ("PageDown", Action::PageDown),
+        ("Shift-End", Action::ScrollToBottom),
+        ("Ctrl-End", Action::ScrollToBottom),
+        ("Ctrl-c", Action::Quit),
+        ("Ctrl-d", Action::Quit),
+    ];
+    defaults.iter().filter_map(|&(key, action)| parse_key(key).map(|key| (key, action))).collect()
+}
+
+/// Parses the name of a key, e.g. `PageUp`, `Ctrl-w`, `Alt-b`, `Shift-Up` or `F5`.
+fn parse_key(name: &str) -> Option<Event> {
+    let key = match name {
+        "Backspace" => Key::Backspace,
+        "Left" => Key::Left,
+        "Right" => Key::Right,
+        "Up" => Key::Up,
+        "Down" => Key::Down,
+        "Home" => Key::Home,
+        "End" => Key::End,
+        "PageUp" => Key::PageUp,
+        "PageDown" => Key::PageDown,
+        "Delete" => Key::Delete,
+        "Insert" => Key::Insert,
+        "Space" => Key::Char(' '),
+        "Tab" => Key::Char('\t'),
+        "BackTab" => Key::BackTab,
+        "Enter" => Key::Char('\n'),
+        "Esc" => Key::Esc,
+        _ => {
+            let (modifier, rest) = match name.find('-') {
+                Some(i) if i > 0 && i + 1 < name.len() => (&name[..i], &name[i + 1..]),
+                _ => ("", name),
+            };
+            let mut chars = rest.chars();
+            match (modifier, chars.next(), chars.next()) {
+                ("", Some(c), None) => Key::Char(c),
+                ("Ctrl", Some(c), None) => Key::Ctrl(c),
+                ("Alt", Some(c), None) => Key::Alt(c),
+                ("", Some('F'), Some(_)) => Key::F(rest[1..].parse().ok()?),
+                // termion does not parse keys with modifiers, so we match their escape sequences.
+                (_, _, _) => return modified_key(modifier, rest),
+            }
+        }
+    };
+    Some(Event::Key(key))
+}
+
+/// The escape sequence of a navigation key pressed along with a modifier.
+fn modified_key(modifier: &str, key: &str) -> Option<Event> {
+    let modifier = match modifier {
+        "Shift" => '2',
+        "Alt" => '3',
+        "Ctrl" => '5',
+        _ => return None,
+    };
+    let key = match key {
+        "Up" => 'A',
+        "Down" => 'B',
+        "Right" => 'C',
+        "Left" => 'D',
+        "End" => 'F',
+        "Home" => 'H',
+        _ => return None,
+    };
+    Some(Event::Unsupported(format!("\x1B[1;{}{}", modifier, key).into_bytes()))
+}
+
+#[cfg(test)]
+mod tests {
+    use termion::event::{Event, Key};
+
+    use super::parse_key;
+
+    #[test]
+    fn parse_named_keys() {
+        assert_eq!(parse_key("PageUp"), Some(Event::Key(Key::PageUp)));
+        assert_eq!(parse_key("Enter"), Some(Event::Key(Key::Char('\n'))));
+        assert_eq!(parse_key("Space"), Some(Event::Key(Key::Char(' '))));
+        assert_eq!(parse_key("F5"), Some(Event::Key(Key::F(5))));
+        assert_eq!(parse_key("F12"), Some(Event::Key(Key::F(12))));
+        assert_eq!(parse_key("a"), Some(Event::Key(Key::Char('a'))));
+        assert_eq!(parse_key("-"), Some(Event::Key(Key::Char('-'))));
+    }
+
+    #[test]
+    fn parse_modified_keys() {
+        assert_eq!(parse_key("Ctrl-w"), Some(Event::Key(Key::Ctrl('w'))));
+        assert_eq!(parse_key("Alt-b"), Some(Event::Key(Key::Alt('b'))));
+        assert_eq!(parse_key("Alt--"), Some(Event::Key(Key::Alt('-'))));
+        assert_eq!(parse_key("Shift-Up"), Some(Event::Unsupported(b"\x1B[1;2A".to_vec())));
+        assert_eq!(parse_key("Alt-Down"), Some(Event::Unsupported(b"\x1B[1;3B".to_vec())));
+        assert_eq!(parse_key("Ctrl-End"), Some(Event::Unsupported(b"\x1B[1;5F".to_vec())));
+        assert_eq!(parse_key("Shift-Home"), Some(Event::Unsupported(b"\x1B[1;2H".to_vec())));
+    }
+
+    #[test]
+    fn parse_unknown_keys() {
+        assert_eq!(parse_key(""), None);
+        assert_eq!(parse_key("Foo"), None);
+        assert_eq!(parse_key("Fx"), None);
+        assert_eq!(parse_key("Super-a"), None);
+        assert_eq!(parse_key("Ctrl-ab"), None);
+        assert_eq!(parse_key("Shift-PageUp"), None);
+        assert_eq!(parse_key("Ctrl-"), None);
+    }
+}
@@ -0,0 +1,267 @@
+use irc::proto::ChannelExt;
+
+use error;
+
+/// The kinds of arguments that commands take.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ArgKind {
+    /// A single word.
+    Word,
+    /// A single word that must be a channel name.
+    Channel,
+    /// The rest of the line, spaces included.
+    Text,
+}
+
+#[derive(Clone, Debug)]
+pub struct Arg {
+    pub name: &'static str,
+    pub kind: ArgKind,
+    pub required: bool,
+}
+
+impl Arg {
+    pub fn required(name: &'static str, kind: ArgKind) -> Arg {
+        Arg {
+            name: name,
+            kind: kind,
+            required: true,
+        }
+    }
+
+    pub fn optional(name: &'static str, kind: ArgKind) -> Arg {
+        Arg {
+            name: name,
+            kind: kind,
+            required: false,
+        }
+    }
+}
+
+/// The arguments that a command was invoked with, in the order that the command declares them.
+pub struct Args<'a> {
+    values: Vec<Option<&'a str>>,
+}
+
+impl<'a> Args<'a> {
+    pub fn get(&self, idx: usize) -> Option<&'a str> {
+        self.values.get(idx).and_then(|value| *value)
+    }
+
+    /// Gets an argument that the command declared as required, and so is always present.
+    pub fn required(&self, idx: usize) -> &'a str {
+        self.get(idx).expect("required argument was not parsed")
+    }
+}
+
+/// A slash command, handled by a function of the context `T`.
+pub struct Command<T> {
+    pub name: &'static str,
+    pub aliases: Vec<&'static str>,
+    pub args: Vec<Arg>,
+    pub help: &'static str,
+    pub handler: fn(&T, &Args) -> error::Result<()>,
+}
+
+impl<T> Command<T> {
+    pub fn new(name: &'static str, handler: fn(&T, &Args) -> error::Result<()>) -> Command<T> {
+        Command {
+            name: name,
+            aliases: Vec::new(),
+            args: Vec::new(),
+            help: "",
+            handler: handler,
+        }
+    }
+
+    pub fn alias(mut self, alias: &'static str) -> Command<T> {
+        self.aliases.push(alias);
+        self
+    }
+
+    pub fn arg(mut self, arg: Arg) -> Command<T> {
+        self.args.push(arg);
+        self
+    }
+
+    pub fn help(mut self, help: &'static str) -> Command<T> {
+        self.help = help;
+        self
+    }
+
+    /// The usage of the command, e.g. `/query <nick> [message]`.
+    pub fn usage(&self) -> String {
+        self.args.iter().fold(format!("/{}", self.name), |usage, arg| {
+            if arg.required {
+                format!("{} <{}>", usage, arg.name)
+            } else {
+                format!("{} [{}]", usage, arg.name)
+            }
+        })
+    }
+
+    /// Parses the arguments of the command from the rest of the line.
+    fn parse_args<'a>(&self, line: &'a str) -> error::Result<Args<'a>> {
+        let invalid = |reason: String| error::Error::InvalidCommandArgs {
+            reason: reason,
+            usage: self.usage(),
+        };
+
+        let mut rest = line.trim_start();
+        let mut values = Vec::with_capacity(self.args.len());
+        for arg in &self.args {
+            if rest.is_empty() {
+                if arg.required {
+                    return Err(invalid(format!("missing argument <{}>", arg.name)));
+                }
+                values.push(None);
+                continue;
+            }
+
+            let value = match arg.kind {
+                ArgKind::Text => rest,
+                ArgKind::Word | ArgKind::Channel => &rest[..rest.find(' ').unwrap_or(rest.len())],
+            };
+            if arg.kind == ArgKind::Channel && !value.is_channel_name() {
+                return Err(invalid(format!("`{}` is not a channel", value)));
+            }
+            rest = rest[value.len()..].trim_start();
+            values.push(Some(value));
+        }
+
+        if !rest.is_empty() {
+            return Err(invalid(format!("unexpected argument `{}`", rest)));
+        }
+
+        Ok(Args {
+            values: values,
+        })
+    }
+}
+
+/// The set of commands available to the user.
+pub struct Registry<T> {
+    commands: Vec<Command<T>>,
+}
+
+impl<T> Registry<T> {
+    pub fn new() -> Registry<T> {
+        Registry {
+            commands: Vec::new(),
+        }
+    }
+
+    pub fn add(&mut self, command: Command<T>) {
+        self.commands.push(command);
+    }
+
+    /// Looks up a command by its name or one of its aliases.
+    pub fn get(&self, name: &str) -> Option<&Command<T>> {
+        self.commands.iter().find(|command| {
+            command.name == name || command.aliases.contains(&name)
+        })
+    }
+
+    pub fn iter(&self) -> ::std::slice::Iter<Command<T>> {
+        self.commands.iter()
+    }
+
+    /// Parses a command line, without its leading slash, into a command and its arguments.
+    pub fn parse<'a>(&self, line: &'a str) -> error::Result<(&Command<T>, Args<'a>)> {
+        let (name, rest) = match line.find(' ') {
+            Some(i) => (&line[..i], &line[i..]),
+            None => (line, ""),
+        };
+        let command = self.get(name).ok_or_else(|| {
+            error::Error::UnknownCommand { name: name.to_owned() }
+        })?;
+        command.parse_args(rest).map(|args| (command, args))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use error;
+    use super::{Arg, ArgKind, Args, Command, Registry};
+
+    fn noop(_: &(), _: &Args) -> error::Result<()> {
+        Ok(())
+    }
+
+    fn registry() -> Registry<()> {
+        let mut registry = Registry::new();
+        registry.add(
+            Command::new("join", noop)
+                .arg(Arg::required("channel", ArgKind::Channel))
+                .arg(Arg::optional("key", ArgKind::Word))
+        );
+        registry.add(
+            Command::new("msg", noop).alias("m")
+                .arg(Arg::required("target", ArgKind::Word))
+                .arg(Arg::required("message", ArgKind::Text))
+        );
+        registry.add(Command::new("part", noop).arg(Arg::optional("reason", ArgKind::Text)));
+        registry
+    }
+
+    /// The reason that the line's arguments are invalid.
+    fn invalid(line: &str) -> String {
+        match registry().parse(line) {
+            Err(error::Error::InvalidCommandArgs { reason, .. }) => reason,
+            Err(e) => panic!("unexpected error for {:?}: {}", line, e),
+            Ok(_) => panic!("parsed {:?}", line),
+        }
+    }
+
+    #[test]
+    fn parse_words_and_text() {
+        let registry = registry();
+        let (command, args) = registry.parse("msg  nick   hello   there ").unwrap();
+        assert_eq!(command.name, "msg");
+        assert_eq!(args.get(0), Some("nick"));
+        assert_eq!(args.get(1), Some("hello   there "));
+
+        let (command, args) = registry.parse("m #chan hi").unwrap();
+        assert_eq!(command.name, "msg");
+        assert_eq!((args.get(0), args.get(1)), (Some("#chan"), Some("hi")));
+    }
+
+    #[test]
+    fn parse_channels() {
+        let registry = registry();
+        let (_, args) = registry.parse("join #chan").unwrap();
+        assert_eq!((args.get(0), args.get(1)), (Some("#chan"), None));
+        let (_, args) = registry.parse("join #chan secret").unwrap();
+        assert_eq!((args.get(0), args.get(1)), (Some("#chan"), Some("secret")));
+        assert_eq!(invalid("join chan"), "`chan` is not a channel");
+    }
+
+    #[test]
+    fn parse_missing_args() {
+        assert_eq!(invalid("join"), "missing argument <channel>");
+        assert_eq!(invalid("join   "), "missing argument <channel>");
+        assert_eq!(invalid("msg nick"), "missing argument <message>");
+        let (_, args) = registry().parse("part").unwrap();
+        assert_eq!(args.get(0), None);
+    }
+
+    #[test]
+    fn parse_extra_args() {
+        assert_eq!(invalid("join #chan secret more"), "unexpected argument `more`");
+    }
+
+    #[test]
+    fn parse_unknown_command() {
+        match registry().parse("frobnicate now") {
+            Err(error::Error::UnknownCommand { name }) => assert_eq!(name, "frobnicate"),
+            _ => panic!("expected an unknown command"),
+        }
+    }
+
+    #[test]
+    fn usage() {
+        let registry = registry();
+        assert_eq!(registry.get("join").unwrap().usage(), "/join <channel> [key]");
+        assert_eq!(registry.get("m").unwrap().usage(), "/msg <target> <message>");
+    }
+}
@@ -1,74 +1,128 @@
+use std::collections::HashMap;
+
 use irc::client::prelude::*;
 use termion::event::{Event, Key};
 
+use config::{Action, Config};
+use controller::command;
+use controller::command::{Arg, ArgKind, Args, Registry};
 use error;
 use model;
+use model::BufferId;
+use utils;
 use view::UI;
 
+/// The longest line that servers accept, including the trailing CRLF.
+const MAX_LINE_LEN: usize = 512;
+
+/// The longest hostname that servers show, which is assumed until our own host is known.
+const MAX_HOST_LEN: usize = 63;
+
+/// The CTCP framing around the text of an action.
+const ACTION_START: &str = "\x01ACTION ";
+const ACTION_END: &str = "\x01";
+
+/// Shown when the raw buffer is opened, as it does not show the lines from before.
+const RAWLOG_NOTE: &str = "This buffer shows every line sent to and received from the network \
+    while it is open.";
+
 pub struct InputController {
-    client: IrcClient,
+    /// The client for each network, by the name of its status buffer.
+    clients: HashMap<String, IrcClient>,
+    keybindings: HashMap<Event, Action>,
+    commands: Registry<InputController>,
+    /// The number of lines past which messages to channels must be confirmed.
+    paste_confirm_lines: usize,
     ui: UI,
 }
 
 impl InputController {
-    pub fn new(client: IrcClient, ui: UI) -> InputController {
+    pub fn new(ui: UI, config: &Config) -> InputController {
         InputController {
-            client: client,
+            clients: HashMap::new(),
+            keybindings: config.keybindings.clone(),
+            commands: commands(),
+            paste_confirm_lines: config.ui.paste_confirm_lines,
             ui: ui,
         }
     }
 
+    /// Adds the client for a network, to which commands in that network's buffers are sent.
+    pub fn add_client(&mut self, server: &str, client: IrcClient) {
+        self.clients.insert(server.to_owned(), client);
+    }
+
     pub fn ui(&self) -> &UI {
         &self.ui
     }
 
     pub fn handle_event(&self, event: Event) -> error::Result<()> {
-        if let Event::Key(key) = event {
-            match key {
-                Key::Ctrl('c') | Key::Ctrl('d') => {
-                    self.client.send_quit("QUIT")?;
-                    return Err(error::Error::UserQuit);
-                }
+        if let Some(&action) = self.keybindings.get(&event) {
+            return self.perform(action);
+        }
+        if self.search_key(&event)? {
+            return Ok(());
+        }
+
+        match event {
+            Event::Key(key) => match key {
                 Key::Char('\n') => {
-                    let mut input = self.ui.input().unwrap();
-                    if input.get_content().starts_with('/') {
-                        let tokens: Vec<_> = input.get_content().split(' ').collect();
-                        match &tokens[0][1..] {
-                            "switch" => if tokens.len() >= 2 {
-                                self.ui.switch_to(tokens[1])?;
-                            },
-                            "join" => if tokens.len() >= 2 {
-                                self.client.send_join(tokens[1])?;
-                                self.ui.new_chat_buf(tokens[1])?;
-                                self.ui.switch_to(tokens[1])?;
-                            },
-                            "part" => if tokens.len() >= 2 {
-                                self.client.send_part(tokens[1])?;
-                                self.ui.remove_chat_buf(tokens[1])?;
-                            },
-                            "quit" => {
-                                self.client.send_quit("QUIT")?;
-                                return Err(error::Error::UserQuit);
-                            }
-                            _ => (),
-                        }
-                    } else {
-                        let chan = &*self.ui.current_buf()?.to_owned();
-                        self.client.send_privmsg(chan, input.get_content())?;
-
-                        let nick = self.client.current_nickname();
-                        self.ui.add_event_to_current_chat_buf(
-                            model::Event::message(Some(&nick), chan, input.get_content())
-                        )?;
-                    }
-                    input.reset();
+                    self.submit()?;
+                }
+                Key::Alt('\r') | Key::Alt('\n') => {
+                    self.ui.input()?.add_char('\n');
+                }
+                Key::Char('\t') => {
+                    self.complete(false)?;
+                }
+                Key::BackTab => {
+                    self.complete(true)?;
                 }
                 Key::Char(c) => {
                     self.ui.input()?.add_char(c);
                 }
-                Key::Backspace => {
+                Key::Backspace | Key::Ctrl('h') => {
                     self.ui.input()?.backspace();
                 }
+                Key::Delete => {
+                    self.ui.input()?.delete();
+                }
+                Key::Home | Key::Ctrl('a') => {
+                    self.ui.input()?.move_home();
+                }
+                Key::End | Key::Ctrl('e') => {
+                    self.ui.input()?.move_end();
+                }
+                Key::Alt('b') => {
+                    self.ui.input()?.move_word_left();
+                }
+                Key::Alt('f') => {
+                    self.ui.input()?.move_word_right();
+                }
+                Key::Ctrl('w') => {
+                    self.ui.input()?.kill_word_before();
+                }
+                Key::Alt('\x7f') | Key::Alt('\x08') => {
+                    self.ui.input()?.kill_alphanumeric_before();
+                }
+                Key::Ctrl('k') => {
+                    self.ui.input()?.kill_to_end();
+                }
+                Key::Ctrl('u') => {
+                    self.ui.input()?.kill_to_start();
+                }
+                Key::Ctrl('y') => {
+                    self.ui.input()?.yank();
+                }
+                Key::Alt('y') => {
+                    self.ui.input()?.yank_pop();
+                }
+                Key::Ctrl('t') => {
+                    self.ui.input()?.transpose();
+                }
+                Key::Ctrl('r') => {
+                    self.ui.input()?.start_search();
+                }
                 Key::Left => {
                     self.ui.input()?.move_left();
                 }
@@ -82,9 +136,422 @@ impl InputController {
                     self.ui.input()?.move_down();
                 }
                 _ => (),
+            },
+            _ => (),
+        }
+
+        Ok(())
+    }
+
+    /// Handles a key while searching the history, returning whether the search used it. Any key
+    /// that the search does not use ends it, keeping the line that was found.
+    fn search_key(&self, event: &Event) -> error::Result<bool> {
+        let mut input = self.ui.input()?;
+        if !input.is_searching() {
+            return Ok(false);
+        }
+        match event {
+            &Event::Key(Key::Ctrl('r')) => input.search_next(),
+            &Event::Key(Key::Ctrl('g')) | &Event::Key(Key::Esc) => input.cancel_search(),
+            &Event::Key(Key::Backspace) => input.search_pop(),
+            &Event::Key(Key::Char(c)) if c != '\n' && c != '\t' => input.search_push(c),
+            _ => {
+                input.accept_search();
+                return Ok(false);
+            }
+        }
+        Ok(true)
+    }
+
+    /// Inserts pasted text into the input, where it can be edited before it is sent.
+    pub fn handle_paste(&self, text: &str) -> error::Result<()> {
+        self.ui.input()?.add_str(text);
+        Ok(())
+    }
+
+    /// Sends the message in the input, or runs it if it is a command. Each line of a message is
+    /// sent separately, and messages of many lines to a channel are only sent once confirmed.
+    fn submit(&self) -> error::Result<()> {
+        let buf = self.ui.current_buf()?.clone();
+        let message = {
+            let mut input = self.ui.input()?;
+            let lines = input.line_count();
+            if lines > self.paste_confirm_lines && buf.name.is_channel_name() &&
+                !input.is_awaiting_confirmation() {
+                input.await_confirmation();
+                drop(input);
+                return self.show_info(&format!(
+                    "Press Enter again to send {} lines to {}, or edit the message to cancel.",
+                    lines, buf.name
+                ));
+            }
+            let message = input.get_content().to_owned();
+            input.reset();
+            message
+        };
+        if message.is_empty() {
+            return Ok(());
+        }
+        // The message is sent even if the history cannot be saved.
+        if let Err(e) = self.ui.add_to_history(&buf, &message) {
+            self.show_error(&e.to_string())?;
+        }
+
+        if message.starts_with('/') && !message.contains('\n') {
+            return self.run_command(&message[1..]);
+        }
+        // There is nobody on the other end of a status or raw buffer.
+        if !buf.is_conversation() {
+            return self.show_error("there is nobody to send messages to here");
+        }
+        self.ui.scroll_to_bottom()?;
+        for line in message.split('\n').filter(|line| !line.is_empty()) {
+            self.send_message(&buf, line)?;
+        }
+        Ok(())
+    }
+
+    /// Completes the word before the cursor, or moves on to the next completion if the last key
+    /// pressed completed a word already.
+    fn complete(&self, backwards: bool) -> error::Result<()> {
+        let (start, word) = {
+            let mut input = self.ui.input()?;
+            if input.is_completing() {
+                input.cycle_completion(backwards);
+                return Ok(());
+            }
+            let (start, word) = input.word_before_cursor();
+            (start, word.to_owned())
+        };
+        let candidates = self.completions(start, &word)?;
+        self.ui.input()?.complete(candidates, backwards);
+        Ok(())
+    }
+
+    /// The completions of a word that starts at the given index of the input line: commands at
+    /// the start of the line, channels on this network, or else the nicknames in this buffer.
+    fn completions(&self, start: usize, word: &str) -> error::Result<Vec<String>> {
+        let buf = self.ui.current_buf()?.clone();
+        let mut candidates: Vec<_> = if start == 0 && word.starts_with('/') {
+            self.commands.iter().map(|command| format!("/{} ", command.name)).collect()
+        } else if word.is_channel_name() {
+            self.ui.buffers(&buf.server)?.into_iter().filter(|id| {
+                id.name.is_channel_name()
+            }).map(|id| format!("{} ", id.name)).collect()
+        } else {
+            let mut nicks = self.ui.nicknames(&buf)?;
+            if buf.is_conversation() && !buf.name.is_channel_name() {
+                nicks.push(buf.name.clone());
+            }
+            if let Ok(client) = self.client(&buf.server) {
+                nicks.retain(|nick| nick != client.current_nickname());
+            }
+            // Nicknames at the start of the line address the message to that user.
+            let suffix = if start == 0 { ": " } else { " " };
+            nicks.into_iter().map(|nick| format!("{}{}", nick, suffix)).collect()
+        };
+
+        let word = word.to_lowercase();
+        candidates.retain(|candidate| candidate.to_lowercase().starts_with(&word));
+        candidates.sort_by_key(|candidate| candidate.to_lowercase());
+        candidates.dedup();
+        Ok(candidates)
+    }
+
+    /// Runs a command line, without its leading slash. Mistakes in the command are shown to the
+    /// user rather than returned.
+    fn run_command(&self, line: &str) -> error::Result<()> {
+        let result = self.commands.parse(line).and_then(|(command, args)| {
+            (command.handler)(self, &args)
+        });
+        match result {
+            Err(e @ error::Error::UnknownCommand { .. }) |
+            Err(e @ error::Error::InvalidCommandArgs { .. }) |
+            Err(e @ error::Error::CommandFailed { .. }) => self.show_error(&e.to_string()),
+            result => result,
+        }
+    }
+
+    fn show_error(&self, message: &str) -> error::Result<()> {
+        let buf = self.ui.current_buf()?.clone();
+        self.ui.add_event_to_chat_buf(&buf, model::Event::error(&buf.name, message))
+    }
+
+    /// Shows a line of informational output from a command in the current buffer.
+    fn show_info(&self, message: &str) -> error::Result<()> {
+        let buf = self.ui.current_buf()?.clone();
+        self.ui.add_event_to_chat_buf(&buf, model::Event::notice(None, &buf.name, message))
+    }
+
+    /// The current buffer, which must be a channel.
+    fn current_channel(&self) -> error::Result<BufferId> {
+        let buf = self.ui.current_buf()?.clone();
+        if buf.name.is_channel_name() {
+            Ok(buf)
+        } else {
+            Err(error::Error::CommandFailed { reason: format!("{} is not a channel", buf.name) })
+        }
+    }
+
+    fn help(&self, args: &Args) -> error::Result<()> {
+        match args.get(0).map(|name| name.trim_start_matches('/')) {
+            Some(name) => match self.commands.get(name) {
+                Some(command) => {
+                    self.show_info(&format!("{} - {}", command.usage(), command.help))?;
+                    if !command.aliases.is_empty() {
+                        let aliases: Vec<_> = command.aliases.iter().map(|alias| {
+                            format!("/{}", alias)
+                        }).collect();
+                        self.show_info(&format!("aliases: {}", aliases.join(", ")))?;
+                    }
+                    Ok(())
+                }
+                None => Err(error::Error::UnknownCommand { name: name.to_owned() }),
+            },
+            None => {
+                self.show_info("Commands (see /help <command> for more):")?;
+                for command in self.commands.iter() {
+                    self.show_info(&format!("  {} - {}", command.usage(), command.help))?;
+                }
+                Ok(())
+            }
+        }
+    }
+
+    fn switch(&self, args: &Args) -> error::Result<()> {
+        let name = args.required(0);
+        // Networks are switched to by name, buffers within the current network otherwise.
+        let buf = if self.clients.contains_key(name) {
+            BufferId::status(name)
+        } else {
+            BufferId::new(&self.ui.current_buf()?.server, name)
+        };
+        if !self.ui.has_chat_buf(&buf)? {
+            return Err(error::Error::CommandFailed { reason: format!("no buffer named {}", name) });
+        }
+        self.ui.switch_to(&buf)
+    }
+
+    fn join(&self, args: &Args) -> error::Result<()> {
+        let chan = args.required(0);
+        let buf = BufferId::new(&self.ui.current_buf()?.server, chan);
+        self.send(&buf.server, Command::JOIN(chan.to_owned(), None, None))?;
+        if !self.ui.has_chat_buf(&buf)? {
+            self.ui.new_chat_buf(&buf)?;
+        }
+        self.ui.switch_to(&buf)
+    }
+
+    fn part(&self, args: &Args) -> error::Result<()> {
+        let current = self.ui.current_buf()?.clone();
+        let buf = match args.get(0) {
+            Some(name) => BufferId::new(&current.server, name),
+            None => current,
+        };
+        if buf.is_status() || !self.ui.has_chat_buf(&buf)? {
+            return Err(error::Error::CommandFailed {
+                reason: format!("cannot part {}", buf.name),
+            });
+        }
+        // Query buffers are simply closed.
+        if buf.name.is_channel_name() {
+            self.send(&buf.server, Command::PART(buf.name.clone(), None))?;
+        }
+        self.ui.remove_chat_buf(&buf)
+    }
+
+    fn query(&self, args: &Args) -> error::Result<()> {
+        let buf = BufferId::new(&self.ui.current_buf()?.server, args.required(0));
+        if !self.ui.has_chat_buf(&buf)? {
+            self.ui.new_chat_buf(&buf)?;
+        }
+        self.ui.switch_to(&buf)?;
+        match args.get(1) {
+            Some(message) => self.send_message(&buf, message),
+            None => Ok(()),
+        }
+    }
+
+    /// Sends a message without switching buffers. As with the messages we receive, a message to
+    /// a user opens a query buffer with them.
+    fn msg(&self, args: &Args) -> error::Result<()> {
+        let buf = BufferId::new(&self.ui.current_buf()?.server, args.required(0));
+        if !buf.name.is_channel_name() && !self.ui.has_chat_buf(&buf)? {
+            self.ui.new_chat_buf(&buf)?;
+        }
+        self.send_message(&buf, args.required(1))
+    }
+
+    fn topic(&self, args: &Args) -> error::Result<()> {
+        let buf = self.current_channel()?;
+        let topic = args.get(0).map(|topic| topic.to_owned());
+        self.send(&buf.server, Command::TOPIC(buf.name.clone(), topic))
+    }
+
+    fn me(&self, args: &Args) -> error::Result<()> {
+        let buf = self.ui.current_buf()?.clone();
+        if !buf.is_conversation() {
+            return Err(error::Error::CommandFailed {
+                reason: "there is nobody to send actions to here".to_owned(),
+            });
+        }
+        let framing = ACTION_START.len() + ACTION_END.len();
+        let budget = self.privmsg_budget(&buf)?.saturating_sub(framing);
+        self.ui.scroll_to_bottom()?;
+        for action in utils::split_bytes(args.required(0), budget) {
+            self.send(&buf.server, Command::PRIVMSG(
+                buf.name.clone(), format!("{}{}{}", ACTION_START, action, ACTION_END)
+            ))?;
+            let nick = self.client(&buf.server)?.current_nickname();
+            let event = model::Event::action(Some(nick), &buf.name, action);
+            self.ui.add_event_to_chat_buf(&buf, event)?;
+        }
+        Ok(())
+    }
+
+    fn quote(&self, args: &Args) -> error::Result<()> {
+        let line = args.required(0);
+        let message: Message = line.parse().map_err(|e| error::Error::CommandFailed {
+            reason: format!("`{}` is not a valid IRC line: {}", line, e),
+        })?;
+        let server = self.ui.current_buf()?.server.clone();
+        self.send(&server, message)
+    }
+
+    /// Opens the raw buffer of the current network, or closes it if it is open.
+    fn rawlog(&self, _: &Args) -> error::Result<()> {
+        let raw = BufferId::raw(&self.ui.current_buf()?.server);
+        if self.ui.has_chat_buf(&raw)? {
+            self.ui.remove_chat_buf(&raw)
+        } else {
+            self.ui.new_chat_buf(&raw)?;
+            self.ui.switch_to(&raw)?;
+            self.show_info(RAWLOG_NOTE)
+        }
+    }
+
+    fn nicklist(&self, _: &Args) -> error::Result<()> {
+        self.ui.toggle_nick_list()
+    }
+
+    fn quit_command(&self, args: &Args) -> error::Result<()> {
+        self.quit(args.get(0).unwrap_or("QUIT"))?;
+        Err(error::Error::UserQuit)
+    }
+
+    /// Performs the action that a key is bound to.
+    fn perform(&self, action: Action) -> error::Result<()> {
+        match action {
+            Action::ScrollUp => self.ui.scroll_up(1),
+            Action::ScrollDown => self.ui.scroll_down(1),
+            Action::PageUp => self.ui.page_up(),
+            Action::PageDown => self.ui.page_down(),
+            Action::ScrollToBottom => self.ui.scroll_to_bottom(),
+            Action::ToggleNickList => self.ui.toggle_nick_list(),
+            Action::Quit => {
+                self.quit("QUIT")?;
+                Err(error::Error::UserQuit)
+            }
+        }
+    }
+
+    /// The client for the specified network.
+    fn client(&self, server: &str) -> error::Result<&IrcClient> {
+        self.clients.get(server).ok_or_else(|| {
+            error::Error::ServerNotFound { server: server.to_owned() }
+        })
+    }
+
+    /// Sends a message to the network.
+    fn send<M: Into<Message>>(&self, server: &str, message: M) -> error::Result<()> {
+        self.client(server)?.send(message.into())?;
+        Ok(())
+    }
+
+    /// Sends a message to the target, and echoes it into the target's buffer, or the current
+    /// buffer if the target does not have one. Messages too long for a single line are split, and
+    /// messages of only whitespace are not sent.
+    fn send_message(&self, target: &BufferId, message: &str) -> error::Result<()> {
+        for message in utils::split_bytes(message, self.privmsg_budget(target)?) {
+            self.send(&target.server, Command::PRIVMSG(target.name.clone(), message.to_owned()))?;
+
+            let nick = self.client(&target.server)?.current_nickname();
+            let event = model::Event::message(Some(nick), &target.name, message);
+            if self.ui.has_chat_buf(target)? {
+                self.ui.add_event_to_chat_buf(target, event)?;
+            } else {
+                self.ui.add_event_to_current_chat_buf(event)?;
             }
         }
+        Ok(())
+    }
+
+    /// The number of bytes of text that fit in a PRIVMSG to the target once the server adds our
+    /// prefix to it. Until the server shows us our hostmask, the longest possible one is assumed.
+    fn privmsg_budget(&self, target: &BufferId) -> error::Result<usize> {
+        let prefix_len = match self.ui.hostmask(&target.server)? {
+            Some(hostmask) => hostmask.len(),
+            None => {
+                let client = self.client(&target.server)?;
+                // Servers add a `~` to usernames that they cannot verify.
+                let user_len = client.config().username().len() + 1;
+                client.current_nickname().len() + 1 + user_len + 1 + MAX_HOST_LEN
+            }
+        };
+        // The line is `:prefix PRIVMSG target :text\r\n`.
+        let overhead = 1 + prefix_len + " PRIVMSG ".len() + target.name.len() + " :".len() + 2;
+        Ok(MAX_LINE_LEN.saturating_sub(overhead))
+    }
 
+    /// Disconnects from every network.
+    fn quit(&self, message: &str) -> error::Result<()> {
+        for server in self.clients.keys() {
+            self.send(server, Command::QUIT(Some(message.to_owned())))?;
+        }
         Ok(())
     }
 }
+
+fn commands() -> Registry<InputController> {
+    let mut commands = Registry::new();
+    commands.add(command::Command::new("help", InputController::help)
+        .arg(Arg::optional("command", ArgKind::Word))
+        .help("lists the commands, or describes the given one"));
+    commands.add(command::Command::new("switch", InputController::switch)
+        .arg(Arg::required("buffer", ArgKind::Word))
+        .help("switches to a buffer on this network, or to another network"));
+    commands.add(command::Command::new("join", InputController::join)
+        .alias("j")
+        .arg(Arg::required("channel", ArgKind::Channel))
+        .help("joins a channel"));
+    commands.add(command::Command::new("part", InputController::part)
+        .arg(Arg::optional("buffer", ArgKind::Word))
+        .help("leaves a channel or closes a query, the current buffer by default"));
+    commands.add(command::Command::new("query", InputController::query)
+        .arg(Arg::required("nick", ArgKind::Word))
+        .arg(Arg::optional("message", ArgKind::Text))
+        .help("opens a private conversation with a user"));
+    commands.add(command::Command::new("msg", InputController::msg)
+        .arg(Arg::required("target", ArgKind::Word))
+        .arg(Arg::required("message", ArgKind::Text))
+        .help("sends a message without switching buffers"));
+    commands.add(command::Command::new("topic", InputController::topic)
+        .arg(Arg::optional("topic", ArgKind::Text))
+        .help("shows or sets the topic of the current channel"));
+    commands.add(command::Command::new("me", InputController::me)
+        .arg(Arg::required("action", ArgKind::Text))
+        .help("sends an action to the current buffer"));
+    commands.add(command::Command::new("quote", InputController::quote)
+        .alias("raw")
+        .arg(Arg::required("line", ArgKind::Text))
+        .help("sends a raw line to the server, e.g. /quote WHOIS nick"));
+    commands.add(command::Command::new("rawlog", InputController::rawlog)
+        .help("shows or hides the lines sent from here and the messages received on this \
+               network, which is not a complete protocol log"));
+    commands.add(command::Command::new("nicklist", InputController::nicklist)
+        .help("shows or hides the nick list"));
+    commands.add(command::Command::new("quit", InputController::quit_command)
+        .arg(Arg::optional("message", ArgKind::Text))
+        .help("disconnects from every network and exits"));
+    commands
+}
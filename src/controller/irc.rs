@@ -1,19 +1,38 @@
-use irc::client::data::User;
+use std::cell::{Cell, RefCell};
+use std::collections::HashMap;
+
+use chrono::{DateTime, Local};
 use irc::client::prelude::*;
-use irc::proto::ChannelExt;
+use irc::proto::{CapSubCommand, ChannelExt};
 
+use config::HighlightConfig;
 use error;
-use model::Event;
+use model::{BufferId, CaseMapping, Event, Highlighter, MemberList};
 use view::UI;
 
+/// Handles the messages from the client for a single network.
 pub struct IrcController {
+    server: String,
     ui: UI,
+    highlighter: Highlighter,
+    /// Whether highlights are also collected in the network's highlights buffer.
+    highlight_buffer: bool,
+    /// How the network compares nicknames, as advertised by the server.
+    casemapping: Cell<CaseMapping>,
+    /// The member lists of channels whose NAMES replies are still arriving, which replace the
+    /// channels' member lists once complete.
+    names: RefCell<HashMap<BufferId, MemberList>>,
 }
 
 impl IrcController {
-    pub fn new(ui: UI) -> IrcController {
+    pub fn new(server: &str, ui: UI, config: &HighlightConfig) -> IrcController {
         IrcController {
+            server: server.to_owned(),
             ui: ui,
+            highlighter: config.highlighter(),
+            highlight_buffer: config.buffer,
+            casemapping: Cell::new(CaseMapping::default()),
+            names: RefCell::new(HashMap::new()),
         }
     }
 
@@ -21,38 +40,195 @@ impl IrcController {
         &self.ui
     }
 
-    pub fn handle_message(&self, message: Message) -> error::Result<()> {
+    pub fn handle_message(&self, client: &IrcClient, message: Message) -> error::Result<()> {
+        self.update_hostmask(client, &message)?;
+
         match &message.command {
             &Command::PRIVMSG(ref chan, ref msg) => {
-                if !chan.is_channel_name() || self.ui.has_chat_buf(chan)? {
-                    self.ui.add_event_to_chat_buf(
-                        chan, Event::message(message.source_nickname(), chan, msg)
-                    )?
+                // CTCP requests other than actions are answered by the client, and not shown.
+                let action = ctcp_action(msg);
+                if action.is_none() && msg.starts_with('\x01') {
+                    return Ok(());
+                }
+
+                if let Some(buf) = self.buffer_for(&message, chan)? {
+                    let event = match action {
+                        Some(action) => Event::action(message.source_nickname(), chan, action),
+                        None => Event::message(message.source_nickname(), chan, msg),
+                    };
+                    let event = self.highlight(client, &message, action.unwrap_or(msg), event);
+                    self.add_event(&message, &buf, event)?
                 }
             }
             &Command::NOTICE(ref chan, ref msg) => {
-                if !chan.is_channel_name() || self.ui.has_chat_buf(chan)? {
-                    self.ui.add_event_to_chat_buf(
-                        chan, Event::notice(message.source_nickname(), chan, msg)
-                    )?
+                if let Some(buf) = self.buffer_for(&message, chan)? {
+                    let event = Event::notice(message.source_nickname(), chan, msg);
+                    let event = self.highlight(client, &message, msg, event);
+                    self.add_event(&message, &buf, event)?
                 }
             }
             &Command::JOIN(ref chan, _, _) => {
-                if !chan.is_channel_name() || self.ui.has_chat_buf(chan)? {
-                    self.ui.add_event_to_chat_buf(
-                        chan, Event::joined(message.source_nickname(), chan)
-                    )?
+                let buf = self.buffer(chan);
+                if !chan.is_channel_name() || self.ui.has_chat_buf(&buf)? {
+                    self.add_event(
+                        &message, &buf, Event::joined(message.source_nickname(), chan)
+                    )?;
+                    if let Some(nickname) = message.source_nickname() {
+                        self.ui.add_member(&buf, nickname, self.casemapping.get())?;
+                    }
                 }
             }
             &Command::PART(ref chan, _) => {
-                if !chan.is_channel_name() || self.ui.has_chat_buf(chan)? {
-                    self.ui.add_event_to_chat_buf(
-                        chan, Event::parted(message.source_nickname(), chan)
+                let buf = self.buffer(chan);
+                if !chan.is_channel_name() || self.ui.has_chat_buf(&buf)? {
+                    self.add_event(
+                        &message, &buf, Event::parted(message.source_nickname(), chan)
+                    )?;
+                    if let Some(nickname) = message.source_nickname() {
+                        self.ui.remove_member(&buf, nickname, self.casemapping.get())?;
+                    }
+                }
+            }
+            &Command::QUIT(ref reason) => {
+                if let Some(nickname) = message.source_nickname() {
+                    let reason = reason.as_ref().map(|s| &s[..]);
+                    let chans = self.ui.remove_member_from_all(
+                        &self.server, nickname, self.casemapping.get()
+                    )?;
+                    for buf in self.shared_buffers(nickname, chans)? {
+                        self.add_event(
+                            &message, &buf, Event::quit(Some(nickname), &buf.name, reason)
+                        )?
+                    }
+                }
+            }
+            &Command::KICK(ref chan, ref nickname, ref reason) => {
+                let buf = self.buffer(chan);
+                if self.ui.has_chat_buf(&buf)? {
+                    let casemapping = self.casemapping.get();
+                    let us = casemapping.to_lower(client.current_nickname());
+                    // Once we are kicked, nothing more is heard of the channel's members or topic.
+                    if casemapping.to_lower(nickname) == us {
+                        self.ui.clear_channel(&buf)?;
+                    } else {
+                        self.ui.remove_member(&buf, nickname, casemapping)?;
+                    }
+                    self.add_event(&message, &buf, Event::kick(
+                        message.source_nickname(), chan, nickname, reason.as_ref().map(|s| &s[..])
+                    ))?
+                }
+            }
+            &Command::NICK(ref new) => {
+                if let Some(old) = message.source_nickname() {
+                    let chans = self.ui.rename_member(
+                        &self.server, old, new, self.casemapping.get()
+                    )?;
+                    for buf in self.shared_buffers(old, chans)? {
+                        self.add_event(&message, &buf, Event::nick(Some(old), &buf.name, new))?
+                    }
+                }
+            }
+            &Command::ChannelMODE(ref chan, ref modes) => {
+                let buf = self.buffer(chan);
+                if self.ui.has_chat_buf(&buf)? {
+                    for mode in modes {
+                        self.ui.update_member_mode(&buf, mode, self.casemapping.get())?;
+                    }
+                    let modes: Vec<_> = modes.iter().map(|mode| mode.to_string()).collect();
+                    let event = Event::mode(message.source_nickname(), chan, &modes.join(" "));
+                    self.add_event(&message, &buf, event)?
+                }
+            }
+            &Command::UserMODE(ref target, ref modes) => {
+                let modes: Vec<_> = modes.iter().map(|mode| mode.to_string()).collect();
+                let event = Event::mode(message.source_nickname(), target, &modes.join(" "));
+                self.add_event(&message, &BufferId::status(&self.server), event)?
+            }
+            &Command::INVITE(ref target, ref chan) => {
+                // Invites are shown wherever the user is looking, so long as it is this network.
+                let buf = {
+                    let current_buf = self.ui.current_buf()?;
+                    if current_buf.server == self.server {
+                        current_buf.clone()
+                    } else {
+                        BufferId::status(&self.server)
+                    }
+                };
+                self.add_event(
+                    &message, &buf, Event::invite(message.source_nickname(), target, chan)
+                )?
+            }
+            &Command::TOPIC(ref chan, Some(ref topic)) => {
+                let buf = self.buffer(chan);
+                if self.ui.has_chat_buf(&buf)? {
+                    self.ui.set_topic(&buf, topic)?;
+                    if let Some(nickname) = message.source_nickname() {
+                        self.ui.set_topic_setter(&buf, nickname)?;
+                    }
+                    self.add_event(
+                        &message, &buf, Event::topic(message.source_nickname(), chan, topic)
                     )?
                 }
             }
+            &Command::Response(Response::RPL_TOPIC, ref args, Some(ref topic)) => {
+                if let Some(chan) = args.iter().find(|s| s.is_channel_name()) {
+                    let buf = self.buffer(chan);
+                    if self.ui.has_chat_buf(&buf)? {
+                        self.ui.set_topic(&buf, topic)?;
+                        self.add_event(&message, &buf, Event::topic(None, chan, topic))?
+                    }
+                }
+            }
+            &Command::Response(Response::RPL_NOTOPIC, ref args, _) => {
+                if let Some(chan) = args.iter().find(|s| s.is_channel_name()) {
+                    let buf = self.buffer(chan);
+                    if self.ui.has_chat_buf(&buf)? {
+                        self.ui.set_topic(&buf, "")?;
+                        self.add_event(
+                            &message, &buf, Event::notice(None, chan, "No topic is set.")
+                        )?
+                    }
+                }
+            }
+            &Command::Response(Response::RPL_TOPICWHOTIME, ref args, _) => {
+                if let (Some(chan), Some(setter)) = (args.get(1), args.get(2)) {
+                    let buf = self.buffer(chan);
+                    if self.ui.has_chat_buf(&buf)? {
+                        // Some servers send the full hostmask of the setter.
+                        let setter = setter.split('!').next().unwrap_or(setter);
+                        self.ui.set_topic_setter(&buf, setter)?;
+                        let notice = format!("Topic set by {}.", setter);
+                        self.add_event(&message, &buf, Event::notice(None, chan, &notice))?
+                    }
+                }
+            }
+            &Command::CAP(_, ref subcommand, _, ref caps) => {
+                let caps = caps.as_ref().map_or("", |caps| caps.trim());
+                let notice = match subcommand {
+                    &CapSubCommand::ACK => format!("Enabled capabilities: {}", caps),
+                    &CapSubCommand::NAK => format!("The server refused capabilities: {}", caps),
+                    _ => return Ok(()),
+                };
+                let status = BufferId::status(&self.server);
+                self.add_event(&message, &status, Event::notice(None, &self.server, &notice))?
+            }
+            &Command::Response(Response::RPL_ISUPPORT, ref args, _) => {
+                let casemapping = args.iter().filter_map(|arg| {
+                    arg.strip_prefix("CASEMAPPING=").and_then(CaseMapping::from_name)
+                }).next();
+                if let Some(casemapping) = casemapping {
+                    self.casemapping.set(casemapping);
+                }
+            }
             &Command::Response(Response::RPL_NAMREPLY, ref args, ref suffix) => {
                 if let Some(chan) = args.iter().find(|s| s.is_channel_name()) {
+                    let buf = self.buffer(chan);
+                    if !self.ui.has_chat_buf(&buf)? {
+                        return Ok(());
+                    }
+
+                    let mut names = self.names.borrow_mut();
+                    let members = names.entry(buf).or_insert_with(MemberList::new);
                     if let Some(users) = suffix.as_ref().map(|s| s.split(" ")) {
                         for user in users {
                             // Skip empty strings.
@@ -60,12 +236,19 @@ impl IrcController {
                                 continue;
                             }
 
-                            // Parses off the access level information.
-                            let user = User::new(user);
-                            let nickname = user.get_nickname();
-                            self.ui.add_event_to_chat_buf(
-                                chan, Event::joined(Some(nickname), chan)
-                            )?
+                            // The member list parses off the access level information.
+                            members.add(user, self.casemapping.get());
+                        }
+                    }
+                }
+            }
+            &Command::Response(Response::RPL_ENDOFNAMES, ref args, _) => {
+                if let Some(chan) = args.iter().find(|s| s.is_channel_name()) {
+                    let buf = self.buffer(chan);
+                    let members = self.names.borrow_mut().remove(&buf);
+                    if let Some(members) = members {
+                        if self.ui.has_chat_buf(&buf)? {
+                            self.ui.set_members(&buf, members)?;
                         }
                     }
                 }
@@ -75,4 +258,109 @@ impl IrcController {
 
         Ok(())
     }
+
+    /// Remembers our own nick!user@host from the messages that we are the source of, so that the
+    /// length of the prefix that the server adds to our messages is known.
+    fn update_hostmask(&self, client: &IrcClient, message: &Message) -> error::Result<()> {
+        let prefix = match message.prefix {
+            Some(ref prefix) if prefix.contains('!') => prefix,
+            _ => return Ok(()),
+        };
+        let nickname = client.current_nickname();
+        let source = message.source_nickname().unwrap_or("");
+        match &message.command {
+            &Command::NICK(ref new) if source == nickname || new == nickname => {
+                let hostmask = format!("{}{}", new, &prefix[source.len()..]);
+                self.ui.set_hostmask(&self.server, &hostmask)
+            }
+            _ if source == nickname => self.ui.set_hostmask(&self.server, prefix),
+            _ => Ok(()),
+        }
+    }
+
+    /// The buffer with the specified name on this network.
+    fn buffer(&self, name: &str) -> BufferId {
+        BufferId::new(&self.server, name)
+    }
+
+    /// Highlights the event if its text, sent by another user, mentions us.
+    fn highlight(&self, client: &IrcClient, message: &Message, text: &str, event: Event) -> Event {
+        let from_user = message.prefix.as_ref().is_some_and(|prefix| prefix.contains('!'));
+        let nickname = client.current_nickname();
+        let casemapping = self.casemapping.get();
+        let from_us = message.source_nickname().is_some_and(|source| {
+            casemapping.to_lower(source) == casemapping.to_lower(nickname)
+        });
+        if from_user && !from_us && self.highlighter.matches(text, nickname, casemapping) {
+            event.highlighted()
+        } else {
+            event
+        }
+    }
+
+    /// Adds the event to the buffer, dated by the server's `time` tag if the message has one.
+    /// Highlights are also added to the network's highlights buffer, if it is enabled.
+    fn add_event(&self, message: &Message, buf: &BufferId, event: Event) -> error::Result<()> {
+        let event = with_server_time(message, event);
+        if event.highlight && self.highlight_buffer {
+            let highlights = BufferId::highlights(&self.server);
+            if !self.ui.has_chat_buf(&highlights)? {
+                self.ui.new_chat_buf(&highlights)?;
+            }
+            self.ui.add_event_to_chat_buf(&highlights, Event::mention(&buf.name, event.clone()))?;
+        }
+        self.ui.add_event_to_chat_buf(buf, event)
+    }
+
+    /// Adds the user's query buffer, if any, to the channels that they shared with us.
+    fn shared_buffers(
+        &self, nickname: &str, mut chans: Vec<BufferId>
+    ) -> error::Result<Vec<BufferId>> {
+        let query = self.buffer(nickname);
+        if self.ui.has_chat_buf(&query)? {
+            chans.push(query);
+        }
+        Ok(chans)
+    }
+
+    /// Determines which buffer a message sent to the target belongs in, opening a query buffer
+    /// for private messages from other users. Returns `None` if the message should be dropped.
+    fn buffer_for(&self, message: &Message, target: &str) -> error::Result<Option<BufferId>> {
+        if target.is_channel_name() {
+            let buf = self.buffer(target);
+            return Ok(if self.ui.has_chat_buf(&buf)? { Some(buf) } else { None });
+        }
+
+        // Only users have a prefix of the form nick!user@host, anything else is a server.
+        let from_user = message.prefix.as_ref().is_some_and(|prefix| prefix.contains('!'));
+        match message.source_nickname() {
+            Some(nickname) if from_user => {
+                let buf = self.buffer(nickname);
+                if !self.ui.has_chat_buf(&buf)? {
+                    self.ui.new_chat_buf(&buf)?;
+                }
+                Ok(Some(buf))
+            }
+            _ => Ok(Some(self.buffer(target))),
+        }
+    }
+}
+
+/// Extracts the text of a CTCP ACTION (i.e. `/me`) from a message, if it is one. An action
+/// without any text is empty rather than not an action.
+fn ctcp_action(msg: &str) -> Option<&str> {
+    let text = msg.strip_prefix("\x01ACTION")?.trim_end_matches('\x01');
+    if text.is_empty() { Some(text) } else { text.strip_prefix(' ') }
+}
+
+/// Dates the event by the message's IRCv3 `server-time` tag, if it has a valid one.
+fn with_server_time(message: &Message, event: Event) -> Event {
+    let time = message.tags.as_ref().and_then(|tags| {
+        tags.iter().find(|tag| tag.0 == "time").and_then(|tag| tag.1.as_ref())
+    }).and_then(|time| DateTime::parse_from_rfc3339(time).ok());
+
+    match time {
+        Some(time) => event.at(time.with_timezone(&Local)),
+        None => event,
+    }
 }
@@ -1,3 +1,4 @@
+mod command;
 mod input;
 mod irc;
 
@@ -2,7 +2,9 @@ use std::io::Error as IoError;
 
 use futures::sync::mpsc::SendError;
 use irc::error::IrcError;
-use termion::event::Event;
+use native_tls::Error as TlsError;
+
+use input::InputEvent;
 
 pub type Result<T> = ::std::result::Result<T, Error>;
 
@@ -12,11 +14,17 @@ pub enum Error {
     Io(#[cause] IoError),
 
     #[fail(display = "failed to send keypress event")]
-    SendKey(#[cause] SendError<Event>),
+    SendKey(#[cause] SendError<InputEvent>),
+
+    #[fail(display = "failed to send resize event")]
+    SendResize(#[cause] SendError<(u16, u16)>),
 
     #[fail(display = "irc error")]
     Irc(#[cause] IrcError),
 
+    #[fail(display = "a tls error occurred")]
+    Tls(#[cause] TlsError),
+
     #[fail(display = "attempted to join on panicked thread. thread panicked with:\n{}", err)]
     ThreadJoinErr {
         err: String,
@@ -32,11 +40,101 @@ pub enum Error {
         chan: String,
     },
 
+    #[fail(display = "failed to look up the specified server: {}", server)]
+    ServerNotFound {
+        server: String,
+    },
+
     #[fail(display = "failed to find the specified tab: {}", tab)]
     TabNotFound {
         tab: String,
     },
 
+    #[fail(display = "invalid timestamp format: {}", format)]
+    InvalidTimestampFormat {
+        format: String,
+    },
+
+    #[fail(display = "failed to read config file {}", path)]
+    ConfigRead {
+        path: String,
+        #[cause] cause: IoError,
+    },
+
+    #[fail(display = "invalid config file {}: {}", path, reason)]
+    InvalidConfig {
+        path: String,
+        reason: String,
+    },
+
+    #[fail(
+        display = "{} is no longer read, move it to {} and put its settings under [[servers]]",
+        path, new_path
+    )]
+    LegacyConfig {
+        path: String,
+        new_path: String,
+    },
+
+    #[fail(display = "no servers are configured, add a [[servers]] table or pass --server")]
+    NoServers,
+
+    #[fail(display = "servers[{}] is missing a {}", index, option)]
+    IncompleteServer {
+        index: usize,
+        option: &'static str,
+    },
+
+    #[fail(display = "more than one server is named {}, give each a different name option", name)]
+    DuplicateNetwork {
+        name: String,
+    },
+
+    #[fail(display = "{}, see --help for usage", reason)]
+    InvalidArgs {
+        reason: String,
+    },
+
+    #[fail(display = "unknown command /{}, see /help for a list of commands", name)]
+    UnknownCommand {
+        name: String,
+    },
+
+    #[fail(display = "{}, usage: {}", reason, usage)]
+    InvalidCommandArgs {
+        reason: String,
+        usage: String,
+    },
+
+    #[fail(display = "{}", reason)]
+    CommandFailed {
+        reason: String,
+    },
+
+    #[fail(display = "failed to read input history from {}", path)]
+    HistoryRead {
+        path: String,
+        #[cause] cause: IoError,
+    },
+
+    #[fail(display = "failed to write input history to {}", path)]
+    HistoryWrite {
+        path: String,
+        #[cause] cause: IoError,
+    },
+
+    #[fail(display = "failed to read chat log {}", path)]
+    LogRead {
+        path: String,
+        #[cause] cause: IoError,
+    },
+
+    #[fail(display = "failed to write chat log to {}", path)]
+    LogWrite {
+        path: String,
+        #[cause] cause: IoError,
+    },
+
     #[fail(display = "the user initiated a quit command")]
     UserQuit,
 }
@@ -47,18 +145,30 @@ impl From<IoError> for Error {
     }
 }
 
-impl From<SendError<Event>> for Error {
-    fn from(e: SendError<Event>) -> Error {
+impl From<SendError<InputEvent>> for Error {
+    fn from(e: SendError<InputEvent>) -> Error {
         Error::SendKey(e)
     }
 }
 
+impl From<SendError<(u16, u16)>> for Error {
+    fn from(e: SendError<(u16, u16)>) -> Error {
+        Error::SendResize(e)
+    }
+}
+
 impl From<IrcError> for Error {
     fn from(e: IrcError) -> Error {
         Error::Irc(e)
     }
 }
 
+impl From<TlsError> for Error {
+    fn from(e: TlsError) -> Error {
+        Error::Tls(e)
+    }
+}
+
 impl From<Error> for IrcError {
     fn from(error: Error) -> IrcError {
         match error {
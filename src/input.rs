@@ -9,11 +9,26 @@ use error;
 use futures::{Async, Poll, Sink, Stream};
 use futures::sync::mpsc;
 use futures::sync::mpsc::UnboundedReceiver;
-use termion::event::Event;
+use signal_hook;
+use signal_hook::iterator::Signals;
+use termion;
+use termion::event::{Event, Key};
 use termion::input::TermRead;
 
+/// The sequences with which the terminal brackets pasted text, which termion does not parse.
+const PASTE_START: &[u8] = b"\x1B[200~";
+const PASTE_END: &[u8] = b"\x1B[201~";
+
+/// Input from the terminal: either a single event, or a whole paste.
+#[derive(Clone, Debug, PartialEq)]
+pub enum InputEvent {
+    Event(Event),
+    /// Text pasted with bracketed paste, in which newlines do not send the message.
+    Paste(String),
+}
+
 pub struct AsyncKeyInput {
-    rx: UnboundedReceiver<Event>,
+    rx: UnboundedReceiver<InputEvent>,
     closed: Arc<AtomicBool>,
     handle: Option<JoinHandle<error::Result<()>>>,
 }
@@ -28,14 +43,12 @@ impl AsyncKeyInput {
             let mut tx = &tx;
             let stdin = io::stdin();
             let input = stdin.events();
+            // The text pasted so far, while within a paste.
+            let mut paste: Option<String> = None;
 
             for res_key in input {
-                match res_key {
-                    Ok(key) => {
-                        if let Err(e) = tx.unbounded_send(key) {
-                            return Err(e.into())
-                        }
-                    }
+                let event = match res_key {
+                    Ok(event) => event,
                     Err(e) => {
                         println!("{:?}", e);
                         if let Err(e) = tx.close() {
@@ -44,6 +57,30 @@ impl AsyncKeyInput {
                         closed_handle.store(true, Ordering::SeqCst);
                         break;
                     }
+                };
+
+                let event = match event {
+                    Event::Unsupported(ref seq) if &seq[..] == PASTE_START => {
+                        paste = Some(String::new());
+                        continue;
+                    }
+                    Event::Unsupported(ref seq) if &seq[..] == PASTE_END => match paste.take() {
+                        Some(text) => InputEvent::Paste(text),
+                        None => continue,
+                    },
+                    // Pasted text arrives as key presses, of which only the characters are kept.
+                    event => match paste {
+                        Some(ref mut text) => {
+                            if let Event::Key(Key::Char(c)) = event {
+                                text.push(c);
+                            }
+                            continue;
+                        }
+                        None => InputEvent::Event(event),
+                    },
+                };
+                if let Err(e) = tx.unbounded_send(event) {
+                    return Err(e.into())
                 }
             }
 
@@ -59,7 +96,7 @@ impl AsyncKeyInput {
 }
 
 impl Stream for AsyncKeyInput {
-    type Item = Event;
+    type Item = InputEvent;
     type Error = error::Error;
 
     fn poll(&mut self) -> Poll<Option<Self::Item>, Self::Error> {
@@ -74,3 +111,65 @@ impl Stream for AsyncKeyInput {
         }
     }
 }
+
+/// A stream of terminal sizes, produced whenever the terminal is resized (i.e. on `SIGWINCH`).
+pub struct AsyncResize {
+    rx: UnboundedReceiver<(u16, u16)>,
+    closed: Arc<AtomicBool>,
+    handle: Option<JoinHandle<error::Result<()>>>,
+}
+
+impl AsyncResize {
+    pub fn new() -> error::Result<AsyncResize> {
+        let (tx, rx) = mpsc::unbounded();
+        let closed = Arc::new(AtomicBool::new(false));
+        let closed_handle = closed.clone();
+        let signals = Signals::new(&[signal_hook::SIGWINCH])?;
+
+        let handle: JoinHandle<error::Result<()>> = thread::spawn(move || {
+            let mut tx = &tx;
+
+            for _ in signals.forever() {
+                match termion::terminal_size() {
+                    Ok(size) => {
+                        if let Err(e) = tx.unbounded_send(size) {
+                            return Err(e.into())
+                        }
+                    }
+                    Err(e) => {
+                        if let Err(e) = tx.close() {
+                            return Err(e.into())
+                        }
+                        closed_handle.store(true, Ordering::SeqCst);
+                        return Err(e.into())
+                    }
+                }
+            }
+
+            Ok(())
+        });
+
+        Ok(AsyncResize {
+            rx: rx,
+            closed: closed,
+            handle: Some(handle),
+        })
+    }
+}
+
+impl Stream for AsyncResize {
+    type Item = (u16, u16);
+    type Error = error::Error;
+
+    fn poll(&mut self) -> Poll<Option<Self::Item>, Self::Error> {
+        if self.closed.load(Ordering::SeqCst) {
+            match self.handle.take().expect("AsyncResize was missing its thread handle.").join() {
+                Ok(Ok(())) => Ok(Async::Ready(None)),
+                Ok(Err(e)) => Err(e),
+                Err(e) => Err(error::Error::ThreadJoinErr { err: format!("{:?}", e) }),
+            }
+        } else {
+            self.rx.poll().map_err(|()| unreachable!())
+        }
+    }
+}
@@ -3,16 +3,30 @@
 
 #[macro_use]
 extern crate failure;
+extern crate chrono;
 extern crate futures;
+extern crate getopts;
 extern crate irc;
+extern crate native_tls;
+extern crate regex;
+extern crate serde;
+#[macro_use]
+extern crate serde_derive;
+extern crate signal_hook;
 extern crate termion;
 extern crate tokio_core;
+extern crate tokio_io;
+extern crate tokio_tls;
+extern crate toml;
 extern crate unicode_segmentation;
 extern crate unicode_width;
 
+pub mod cli;
+pub mod config;
 pub mod controller;
 mod error;
 pub mod input;
 pub mod model;
+pub mod relay;
 mod utils;
 pub mod view;
@@ -1,56 +1,99 @@
 extern crate alectro;
+extern crate failure;
 extern crate futures;
 extern crate irc;
 extern crate termion;
 
 use std::env;
+use std::process;
 
+use alectro::cli::Args;
+use alectro::config::network_name;
 use alectro::controller::{InputController, IrcController};
-use alectro::input::AsyncKeyInput;
+use alectro::input::{AsyncKeyInput, AsyncResize, InputEvent};
+use alectro::model::BufferId;
+use alectro::relay;
 use alectro::view::UI;
-use irc::client::prelude::*;
+use failure::Fail;
+use irc::client::prelude::{Capability, ClientExt, Future, IrcReactor, Stream};
 
 fn main() {
-    let ui = UI::new().unwrap();
-    let mut reactor = IrcReactor::new().unwrap();
+    let program = env::args().next().unwrap_or_else(|| "alectro".to_owned());
+    let args = Args::parse(env::args().skip(1)).unwrap_or_else(|e| exit_with_error(&e));
+    if args.help {
+        print!("{}", Args::usage(&program));
+        return;
+    }
 
-    let default_cfg = Config {
-        nickname: Some(format!("aatxe")),
-        server: Some(format!("irc.mozilla.org")),
-        use_ssl: Some(true),
-        .. Default::default()
-    };
-
-    let cfg = match env::home_dir() {
-        Some(mut path) => {
-            path.push(".alectro");
-            path.set_extension("toml");
-            Config::load(path).unwrap_or(default_cfg)
-        },
-        None => default_cfg,
-    };
-
-    for chan in &cfg.channels() {
-        ui.new_chat_buf(chan).unwrap();
+    let config = args.load_config().unwrap_or_else(|e| exit_with_error(&e));
+    if args.check_config {
+        println!("config ok: {} server(s)", config.servers.len());
+        return;
     }
 
-    let irc_client = reactor.prepare_client_and_connect(&cfg).unwrap();
-    irc_client.identify().unwrap();
+    let ui = UI::new(&config.ui, &config.logging, config.colors).unwrap_or_else(|e| {
+        exit_with_error(&e)
+    });
+    let mut reactor = IrcReactor::new().unwrap();
+    let mut input_controller = InputController::new(ui.clone(), &config);
+
+    for cfg in &config.servers {
+        let server = network_name(cfg).to_owned();
+
+        ui.new_server(&server).unwrap();
+        for chan in &cfg.channels() {
+            ui.new_chat_buf(&BufferId::new(&server, chan)).unwrap();
+        }
+
+        let handle = reactor.inner_handle();
+        let (relayed, relay) = relay::relay(&server, cfg, ui.clone(), &handle).unwrap_or_else(|e| {
+            exit_with_error(&e)
+        });
+        reactor.register_future(relay.map_err(|e| e.into()));
+
+        let irc_client = reactor.prepare_client_and_connect(&relayed).unwrap();
+        // Messages are dated by the server where it supports it, e.g. when a bouncer replays them.
+        irc_client.send_cap_req(&[Capability::ServerTime]).unwrap();
+        irc_client.identify().unwrap();
 
-    let irc_controller = IrcController::new(ui.clone());
-    reactor.register_client_with_handler(irc_client.clone(), move |_, message| {
-        irc_controller.handle_message(message)?;
-        irc_controller.ui().draw_all()?;
+        let irc_controller = IrcController::new(&server, ui.clone(), &config.highlights);
+        reactor.register_client_with_handler(irc_client.clone(), move |client, message| {
+            irc_controller.handle_message(client, message)?;
+            irc_controller.ui().draw_all()?;
+            Ok(())
+        });
+
+        input_controller.add_client(&server, irc_client);
+    }
+
+    let resize_ui = ui.clone();
+    let resize_rx = AsyncResize::new().unwrap();
+    reactor.register_future(resize_rx.for_each(move |(width, height)| {
+        resize_ui.resize(width, height)?;
+        resize_ui.draw_all()?;
         Ok(())
-    });
+    }).map_err(|e| e.into()));
 
-    let input_controller = InputController::new(irc_client, ui);
     let input_rx = AsyncKeyInput::new();
     reactor.register_future(input_rx.for_each(move |event| {
-        input_controller.handle_event(event)?;
+        match event {
+            InputEvent::Event(event) => input_controller.handle_event(event)?,
+            InputEvent::Paste(text) => input_controller.handle_paste(&text)?,
+        }
         input_controller.ui().draw_all()?;
         Ok(())
     }).map_err(|e| e.into()));
 
     reactor.run().unwrap();
 }
+
+/// Prints the error along with everything that caused it, and exits.
+fn exit_with_error<F: Fail>(e: &F) -> ! {
+    eprintln!("error: {}", e);
+    let mut cause = e.cause();
+    while let Some(e) = cause {
+        eprintln!("  caused by: {}", e);
+        cause = e.cause();
+    }
+    process::exit(1)
+}
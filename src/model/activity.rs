@@ -0,0 +1,37 @@
+use std::cmp;
+
+/// How much has happened in a buffer, in increasing order of importance.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum ActivityLevel {
+    None,
+    /// Joins, parts and the like.
+    Noise,
+    Message,
+    /// Mentions of us and private messages.
+    Highlight,
+}
+
+impl Default for ActivityLevel {
+    fn default() -> ActivityLevel {
+        ActivityLevel::None
+    }
+}
+
+/// The activity in a buffer since it was last viewed.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Activity {
+    /// The level of the most important event.
+    pub level: ActivityLevel,
+    /// The number of messages, i.e. events that are not noise.
+    pub unread: usize,
+}
+
+impl Activity {
+    /// Records an event of the specified level.
+    pub fn add(&mut self, level: ActivityLevel) {
+        self.level = cmp::max(self.level, level);
+        if level >= ActivityLevel::Message {
+            self.unread += 1;
+        }
+    }
+}
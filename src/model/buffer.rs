@@ -0,0 +1,56 @@
+use std::fmt;
+
+/// The name of the status buffer of each network, which shows anything not sent to a channel.
+pub const STATUS: &str = "*status*";
+
+/// The name of the buffer that shows the raw protocol lines of each network, when it is open.
+pub const RAW: &str = "*raw*";
+
+/// The name of the buffer that collects the messages that mention us on each network.
+pub const HIGHLIGHTS: &str = "*highlights*";
+
+/// Identifies a buffer by the network that it belongs to and its name on that network.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub struct BufferId {
+    pub server: String,
+    pub name: String,
+}
+
+impl BufferId {
+    pub fn new(server: &str, name: &str) -> BufferId {
+        BufferId {
+            server: server.to_owned(),
+            name: name.to_owned(),
+        }
+    }
+
+    /// The status buffer of the specified network.
+    pub fn status(server: &str) -> BufferId {
+        BufferId::new(server, STATUS)
+    }
+
+    /// The raw protocol buffer of the specified network.
+    pub fn raw(server: &str) -> BufferId {
+        BufferId::new(server, RAW)
+    }
+
+    /// The highlights buffer of the specified network.
+    pub fn highlights(server: &str) -> BufferId {
+        BufferId::new(server, HIGHLIGHTS)
+    }
+
+    pub fn is_status(&self) -> bool {
+        self.name == STATUS
+    }
+
+    /// Whether the buffer belongs to a channel or user that messages can be sent to.
+    pub fn is_conversation(&self) -> bool {
+        self.name != STATUS && self.name != RAW && self.name != HIGHLIGHTS
+    }
+}
+
+impl fmt::Display for BufferId {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}/{}", self.server, self.name)
+    }
+}
@@ -1,9 +1,70 @@
-use std::slice::Iter;
+use std::collections::VecDeque;
+use std::collections::vec_deque::Iter;
 
 use model::Event;
 
+/// The default number of events retained in a buffer's history.
+pub const DEFAULT_CAPACITY: usize = 2048;
+
+/// The history of events in a single buffer, bounded to a fixed capacity.
+#[derive(Clone)]
 pub struct ChatBuf {
-    events: Vec<Event>,
+    events: VecDeque<Event>,
+    capacity: usize,
+}
+
+impl Default for ChatBuf {
+    fn default() -> ChatBuf {
+        ChatBuf::with_capacity(DEFAULT_CAPACITY)
+    }
+}
+
+impl ChatBuf {
+    pub fn new() -> ChatBuf {
+        ChatBuf::default()
+    }
+
+    pub fn with_capacity(capacity: usize) -> ChatBuf {
+        ChatBuf {
+            events: VecDeque::with_capacity(capacity),
+            capacity: capacity,
+        }
+    }
+
+    /// Adds an event to the end of the history, dropping the oldest event if it is full.
+    pub fn push(&mut self, event: Event) {
+        if self.capacity == 0 {
+            return;
+        }
+        if self.events.len() == self.capacity {
+            self.events.pop_front();
+        }
+        self.events.push_back(event)
+    }
+
+    pub fn last(&self) -> Option<&Event> {
+        self.events.back()
+    }
+
+    pub fn iter(&self) -> Iter<Event> {
+        self.events.iter()
+    }
+
+    pub fn len(&self) -> usize {
+        self.events.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.events.is_empty()
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    pub fn clear(&mut self) {
+        self.events.clear()
+    }
 }
 
 impl<'a> IntoIterator for &'a ChatBuf {
@@ -1,70 +1,311 @@
+use chrono::{DateTime, Local};
+use unicode_width::UnicodeWidthStr;
+
+use model::ActivityLevel;
 use utils;
-use view::{Color, Style};
+use view::{Color, Modifier, Style};
+
+/// An event in a buffer, along with the time at which it occurred.
+#[derive(Clone)]
+pub struct Event {
+    pub time: DateTime<Local>,
+    pub kind: EventKind,
+    /// Whether the event mentions us, and so is highlighted.
+    pub highlight: bool,
+}
 
-pub enum Event {
+#[derive(Clone)]
+pub enum EventKind {
     /// sender, target, message
     PrivMessage(Option<String>, String, String),
     /// sender, target, message
     Notice(Option<String>, String, String),
     /// sender, target, joined
     JoinPart(Option<String>, String, bool),
+    /// sender, target, topic
+    Topic(Option<String>, String, String),
+    /// sender, target, reason
+    Quit(Option<String>, String, Option<String>),
+    /// sender, target, new nickname
+    Nick(Option<String>, String, String),
+    /// sender, target, kicked, reason
+    Kick(Option<String>, String, String, Option<String>),
+    /// sender, target, modes
+    Mode(Option<String>, String, String),
+    /// sender, target, channel
+    Invite(Option<String>, String, String),
+    /// sender, target, action
+    Action(Option<String>, String, String),
+    Error(String, String),
+    Raw(String, bool, String),
+    /// target, label
+    Separator(String, String),
+    /// buffer, event
+    Mention(String, Box<Event>),
 }
 
 impl Event {
+    /// Creates an event that occurred now.
+    pub fn new(kind: EventKind) -> Event {
+        Event {
+            time: Local::now(),
+            kind: kind,
+            highlight: false,
+        }
+    }
+
+    /// Sets the time at which the event occurred.
+    pub fn at(mut self, time: DateTime<Local>) -> Event {
+        self.time = time;
+        self
+    }
+
+    /// Marks the event as mentioning us.
+    pub fn highlighted(mut self) -> Event {
+        self.highlight = true;
+        self
+    }
+
     pub fn message(sender: Option<&str>, target: &str, message: &str) -> Event {
-        Event::PrivMessage(sender.map(|s| s.to_owned()), target.to_owned(), message.to_owned())
+        Event::new(EventKind::PrivMessage(
+            sender.map(|s| s.to_owned()), target.to_owned(), message.to_owned()
+        ))
     }
 
     pub fn notice(sender: Option<&str>, target: &str, message: &str) -> Event {
-        Event::Notice(sender.map(|s| s.to_owned()), target.to_owned(), message.to_owned())
+        Event::new(EventKind::Notice(
+            sender.map(|s| s.to_owned()), target.to_owned(), message.to_owned()
+        ))
     }
 
     pub fn joined(sender: Option<&str>, target: &str) -> Event {
-        Event::JoinPart(sender.map(|s| s.to_owned()), target.to_owned(), true)
+        Event::new(EventKind::JoinPart(sender.map(|s| s.to_owned()), target.to_owned(), true))
     }
 
     pub fn parted(sender: Option<&str>, target: &str) -> Event {
-        Event::JoinPart(sender.map(|s| s.to_owned()), target.to_owned(), false)
+        Event::new(EventKind::JoinPart(sender.map(|s| s.to_owned()), target.to_owned(), false))
+    }
+
+    pub fn topic(sender: Option<&str>, target: &str, topic: &str) -> Event {
+        Event::new(EventKind::Topic(
+            sender.map(|s| s.to_owned()), target.to_owned(), topic.to_owned()
+        ))
+    }
+
+    pub fn quit(sender: Option<&str>, target: &str, reason: Option<&str>) -> Event {
+        Event::new(EventKind::Quit(
+            sender.map(|s| s.to_owned()), target.to_owned(), reason.map(|s| s.to_owned())
+        ))
+    }
+
+    pub fn nick(sender: Option<&str>, target: &str, new: &str) -> Event {
+        Event::new(EventKind::Nick(sender.map(|s| s.to_owned()), target.to_owned(), new.to_owned()))
+    }
+
+    pub fn kick(sender: Option<&str>, target: &str, kicked: &str, reason: Option<&str>) -> Event {
+        Event::new(EventKind::Kick(
+            sender.map(|s| s.to_owned()), target.to_owned(), kicked.to_owned(),
+            reason.map(|s| s.to_owned())
+        ))
     }
 
+    pub fn mode(sender: Option<&str>, target: &str, modes: &str) -> Event {
+        Event::new(EventKind::Mode(
+            sender.map(|s| s.to_owned()), target.to_owned(), modes.to_owned()
+        ))
+    }
+
+    pub fn invite(sender: Option<&str>, target: &str, channel: &str) -> Event {
+        Event::new(EventKind::Invite(
+            sender.map(|s| s.to_owned()), target.to_owned(), channel.to_owned()
+        ))
+    }
+
+    pub fn action(sender: Option<&str>, target: &str, action: &str) -> Event {
+        Event::new(EventKind::Action(
+            sender.map(|s| s.to_owned()), target.to_owned(), action.to_owned()
+        ))
+    }
+
+    /// An error to show to the user, e.g. from a mistyped command.
+    pub fn error(target: &str, message: &str) -> Event {
+        Event::new(EventKind::Error(target.to_owned(), message.to_owned()))
+    }
+
+    /// A raw protocol line that was sent, if outgoing, or received.
+    pub fn raw(target: &str, outgoing: bool, line: &str) -> Event {
+        Event::new(EventKind::Raw(target.to_owned(), outgoing, line.to_owned()))
+    }
+
+    /// A line across the buffer that divides its history, e.g. at the end of the backlog.
+    pub fn separator(target: &str, label: &str) -> Event {
+        Event::new(EventKind::Separator(target.to_owned(), label.to_owned()))
+    }
+
+    /// An event that mentioned us in the buffer, as shown in the highlights buffer.
+    pub fn mention(buffer: &str, event: Event) -> Event {
+        let time = event.time;
+        Event::new(EventKind::Mention(buffer.to_owned(), Box::new(event))).at(time)
+    }
+
+    /// How much the event matters to someone who has not seen it yet. Messages are highlights
+    /// when they mention us or were sent privately to us.
+    pub fn activity(&self, private: bool) -> ActivityLevel {
+        match &self.kind {
+            &EventKind::PrivMessage(_, _, _) | &EventKind::Action(_, _, _) |
+            &EventKind::Notice(Some(_), _, _) if private || self.highlight => {
+                ActivityLevel::Highlight
+            }
+            &EventKind::Mention(_, _) => ActivityLevel::Highlight,
+            &EventKind::PrivMessage(_, _, _) | &EventKind::Action(_, _, _) |
+            &EventKind::Notice(Some(_), _, _) | &EventKind::Topic(Some(_), _, _) |
+            &EventKind::Kick(_, _, _, _) | &EventKind::Invite(_, _, _) |
+            &EventKind::Error(_, _) => ActivityLevel::Message,
+            &EventKind::Raw(_, _, _) | &EventKind::Separator(_, _) => ActivityLevel::None,
+            _ => ActivityLevel::Noise,
+        }
+    }
+
+    /// The base style for the event, which bare color codes in the rendered line reset to.
     pub fn style(&self) -> Option<Style> {
-        None
+        match &self.kind {
+            &EventKind::Quit(_, _, _) => Some(Style::from(Color::Red)),
+            &EventKind::Nick(_, _, _) => Some(Style::from(Color::LightBlue)),
+            &EventKind::Kick(_, _, _, _) => {
+                Some(Style::from(Color::LightRed).modifier(Modifier::Bold))
+            }
+            &EventKind::Mode(_, _, _) => Some(Style::from(Color::Yellow)),
+            &EventKind::Invite(_, _, _) => Some(Style::from(Color::Green)),
+            &EventKind::Action(_, _, _) => Some(Style::from(Modifier::Italic)),
+            &EventKind::Error(_, _) => Some(Style::from(Color::LightRed)),
+            &EventKind::Mention(_, ref event) => event.style(),
+            _ => None,
+        }
+    }
+
+    /// The width of the prefix before the message text, which wrapped lines are indented by.
+    pub fn indent(&self) -> usize {
+        match &self.kind {
+            &EventKind::PrivMessage(ref sender, _, _) => {
+                sender.as_ref().map_or(0, |s| s.width()) + 3
+            }
+            &EventKind::Notice(Some(ref sender), _, _) => sender.width() + 5,
+            &EventKind::Notice(None, _, _) | &EventKind::Error(_, _) => 2,
+            &EventKind::Raw(_, _, _) => 3,
+            &EventKind::Action(ref sender, _, _) => {
+                sender.as_ref().map_or(1, |s| s.width()) + 3
+            }
+            &EventKind::Mention(ref buffer, ref event) => buffer.width() + 1 + event.indent(),
+            _ => 0,
+        }
     }
 }
 
 impl ToString for Event {
     fn to_string(&self) -> String {
-        match self {
-            &Event::PrivMessage(ref sender, _, ref message) => {
+        match &self.kind {
+            &EventKind::PrivMessage(ref sender, _, ref message) => {
                 let nick = sender.as_ref().map(|s| &s[..]).unwrap_or("");
                 format!(
                     "\x03{}[{}{}\x03{}]{} {}", Color::Magenta, utils::colorize(nick).to_irc_color(),
                     nick, Color::Magenta, Color::Reset.to_irc_color(), message
                 )
             }
-            &Event::Notice(Some(ref sender), _, ref message) => {
+            &EventKind::Notice(Some(ref sender), _, ref message) => {
                 format!(
                     "\x03{}*[{}\x02{}\x02\x03{}]*{} {}", Color::Magenta,
                     utils::colorize(sender).to_irc_color(), sender, Color::Magenta,
                     Color::Reset.to_irc_color(), message
                 )
             }
-            &Event::Notice(None, _, ref message) => {
+            &EventKind::Notice(None, _, ref message) => {
                 format!("\x03{}*{} {}", Color::Magenta, Color::Reset.to_irc_color(), message)
             }
-            &Event::JoinPart(Some(ref sender), _, true) => {
+            &EventKind::JoinPart(Some(ref sender), _, true) => {
                 format!(
                     "\x03{}+{}{}{}", Color::Green, utils::colorize(sender).to_irc_color(), sender,
                     Color::Reset.to_irc_color()
                 )
             }
-            &Event::JoinPart(Some(ref sender), _, false) => {
+            &EventKind::JoinPart(Some(ref sender), _, false) => {
                 format!(
                     "\x03{}-{}{}{}", Color::Red, utils::colorize(sender).to_irc_color(), sender,
                     Color::Reset.to_irc_color()
                 )
             }
-            &Event::JoinPart(None, _, _) => "".to_owned(),
+            &EventKind::JoinPart(None, _, _) => "".to_owned(),
+            &EventKind::Topic(Some(ref sender), _, ref topic) if topic.is_empty() => {
+                format!(
+                    "\x03{}*{} {}{}{} cleared the topic", Color::Cyan, Color::Reset.to_irc_color(),
+                    utils::colorize(sender).to_irc_color(), sender, Color::Reset.to_irc_color()
+                )
+            }
+            &EventKind::Topic(Some(ref sender), _, ref topic) => {
+                format!(
+                    "\x03{}*{} {}{}{} changed the topic to: {}", Color::Cyan,
+                    Color::Reset.to_irc_color(), utils::colorize(sender).to_irc_color(), sender,
+                    Color::Reset.to_irc_color(), topic
+                )
+            }
+            &EventKind::Topic(None, ref target, ref topic) => {
+                format!(
+                    "\x03{}*{} Topic for {}: {}", Color::Cyan, Color::Reset.to_irc_color(), target,
+                    topic
+                )
+            }
+            &EventKind::Quit(ref sender, _, ref reason) => {
+                format!("<- {} has quit{}", colored_sender(sender), parenthesized(reason))
+            }
+            &EventKind::Nick(ref sender, _, ref new) => {
+                format!("* {} is now known as {}", colored_sender(sender), colored_nick(new))
+            }
+            &EventKind::Kick(ref sender, _, ref kicked, ref reason) => {
+                format!(
+                    "* {} was kicked by {}{}", colored_nick(kicked), colored_sender(sender),
+                    parenthesized(reason)
+                )
+            }
+            &EventKind::Mode(ref sender, ref target, ref modes) => {
+                format!("* {} sets mode {} on {}", colored_sender(sender), modes, target)
+            }
+            &EventKind::Invite(ref sender, _, ref channel) => {
+                format!("* {} invites you to {}", colored_sender(sender), channel)
+            }
+            &EventKind::Action(ref sender, _, ref action) => {
+                format!("* {} {}", colored_sender(sender), action)
+            }
+            &EventKind::Error(_, ref message) => format!("! {}", message),
+            &EventKind::Raw(_, outgoing, ref line) => {
+                // Formatting is shown as-is rather than applied, like the rest of the line.
+                let line: String = line.trim_end_matches("\r\n").chars().flat_map(|c| {
+                    if c.is_ascii_control() {
+                        vec!['^', ((c as u8) ^ 0x40) as char]
+                    } else {
+                        vec![c]
+                    }
+                }).collect();
+                format!("{} {}", if outgoing { ">>" } else { "<<" }, line)
+            }
+            &EventKind::Separator(_, ref label) => label.clone(),
+            &EventKind::Mention(ref buffer, ref event) => {
+                format!("{} {}", colored_nick(buffer), event.to_string())
+            }
         }
     }
 }
+
+/// Renders the nickname in its color, resetting to the line's base color afterwards.
+fn colored_nick(nick: &str) -> String {
+    format!("{}{}{}", utils::colorize(nick).to_irc_color(), nick, Color::Reset.to_irc_color())
+}
+
+fn colored_sender(sender: &Option<String>) -> String {
+    sender.as_ref().map(|s| colored_nick(s)).unwrap_or_else(|| "*".to_owned())
+}
+
+fn parenthesized(text: &Option<String>) -> String {
+    match text {
+        &Some(ref text) if !text.is_empty() => format!(" ({})", text),
+        _ => "".to_owned(),
+    }
+}
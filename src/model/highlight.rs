@@ -0,0 +1,149 @@
+use regex::Regex;
+
+use utils;
+
+/// How a network compares nicknames without regard to case, as given by its `CASEMAPPING`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CaseMapping {
+    Ascii,
+    /// ASCII, along with `[]\~` as the uppercase forms of `{}|^`.
+    Rfc1459,
+    /// RFC 1459, without `~` and `^`.
+    StrictRfc1459,
+}
+
+impl Default for CaseMapping {
+    fn default() -> CaseMapping {
+        CaseMapping::Rfc1459
+    }
+}
+
+impl CaseMapping {
+    pub fn from_name(name: &str) -> Option<CaseMapping> {
+        match name {
+            "ascii" => Some(CaseMapping::Ascii),
+            "rfc1459" => Some(CaseMapping::Rfc1459),
+            "strict-rfc1459" => Some(CaseMapping::StrictRfc1459),
+            _ => None,
+        }
+    }
+
+    /// Lowercases the string under the case mapping.
+    pub fn to_lower(self, s: &str) -> String {
+        s.chars().map(|c| match (self, c) {
+            (_, c) if c.is_ascii_uppercase() => c.to_ascii_lowercase(),
+            (CaseMapping::Ascii, _) => c,
+            (_, '[') => '{',
+            (_, ']') => '}',
+            (_, '\\') => '|',
+            (CaseMapping::Rfc1459, '~') => '^',
+            (_, c) => c,
+        }).collect()
+    }
+}
+
+/// Finds the messages that mention us, by our nickname or by any of the configured words or
+/// patterns.
+#[derive(Clone, Debug, Default)]
+pub struct Highlighter {
+    words: Vec<String>,
+    patterns: Vec<Regex>,
+}
+
+impl Highlighter {
+    pub fn new(words: Vec<String>, patterns: Vec<Regex>) -> Highlighter {
+        Highlighter {
+            words: words,
+            patterns: patterns,
+        }
+    }
+
+    /// Whether the message mentions the nickname, or any of the words, as a whole word and
+    /// without regard to case, or matches any of the patterns. Formatting is ignored.
+    pub fn matches(&self, message: &str, nickname: &str, casemapping: CaseMapping) -> bool {
+        let message = utils::strip_formatting(message);
+        let lower = casemapping.to_lower(&message);
+        let mut words = Some(nickname).into_iter().chain(self.words.iter().map(|word| &word[..]));
+        words.any(|word| contains_word(&lower, &casemapping.to_lower(word))) ||
+            self.patterns.iter().any(|pattern| pattern.is_match(&message))
+    }
+}
+
+fn contains_word(text: &str, word: &str) -> bool {
+    !word.is_empty() && text.match_indices(word).any(|(i, _)| {
+        let before = text[..i].chars().next_back();
+        let after = text[i + word.len()..].chars().next();
+        !before.is_some_and(is_word_char) && !after.is_some_and(is_word_char)
+    })
+}
+
+/// Whether the character can be part of a nickname, and so does not end a word.
+fn is_word_char(c: char) -> bool {
+    c.is_alphanumeric() || "-_[]{}\\|^`".contains(c)
+}
+
+#[cfg(test)]
+mod tests {
+    use regex::Regex;
+
+    use super::{CaseMapping, Highlighter};
+
+    fn matches(highlighter: &Highlighter, message: &str, casemapping: CaseMapping) -> bool {
+        highlighter.matches(message, "al[ex]", casemapping)
+    }
+
+    #[test]
+    fn nickname_as_a_whole_word() {
+        let highlighter = Highlighter::default();
+        for message in &["al[ex]", "al[ex]: hi", "hi al[ex]!", "(al[ex])", "AL[EX], hi"] {
+            assert!(matches(&highlighter, message, CaseMapping::Rfc1459), "{}", message);
+        }
+        // Nickname characters continue a word, so the nickname is only a part of these.
+        for message in &["al[ex]_", "al[ex]s", "hal[ex]", "al[ex]-bot", "al[e x]"] {
+            assert!(!matches(&highlighter, message, CaseMapping::Rfc1459), "{}", message);
+        }
+    }
+
+    #[test]
+    fn nickname_in_a_later_word() {
+        // The first occurrence is part of a longer word, but the second is whole.
+        assert!(matches(&Highlighter::default(), "al[ex]s and al[ex]", CaseMapping::Ascii));
+    }
+
+    #[test]
+    fn casemapping() {
+        let highlighter = Highlighter::default();
+        assert!(matches(&highlighter, "hi al{ex}", CaseMapping::Rfc1459));
+        assert!(matches(&highlighter, "hi AL{EX}", CaseMapping::StrictRfc1459));
+        assert!(!matches(&highlighter, "hi al{ex}", CaseMapping::Ascii));
+        assert!(highlighter.matches("hi a|b^", "A\\B~", CaseMapping::Rfc1459));
+        assert!(!highlighter.matches("hi a|b^", "A\\B~", CaseMapping::StrictRfc1459));
+        assert!(highlighter.matches("hi a|b~", "A\\B~", CaseMapping::StrictRfc1459));
+    }
+
+    #[test]
+    fn casemapping_names() {
+        assert_eq!(CaseMapping::from_name("ascii"), Some(CaseMapping::Ascii));
+        assert_eq!(CaseMapping::from_name("rfc1459"), Some(CaseMapping::Rfc1459));
+        assert_eq!(CaseMapping::from_name("strict-rfc1459"), Some(CaseMapping::StrictRfc1459));
+        assert_eq!(CaseMapping::from_name("rfc7613"), None);
+    }
+
+    #[test]
+    fn words_and_patterns() {
+        let patterns = vec![Regex::new(r"^!deploy\b").unwrap()];
+        let highlighter = Highlighter::new(vec!["Rust".to_owned()], patterns);
+        assert!(matches(&highlighter, "i like rust.", CaseMapping::Ascii));
+        assert!(!matches(&highlighter, "rustacean", CaseMapping::Ascii));
+        assert!(matches(&highlighter, "!deploy now", CaseMapping::Ascii));
+        assert!(!matches(&highlighter, "please !deploy", CaseMapping::Ascii));
+    }
+
+    #[test]
+    fn formatting_is_ignored() {
+        let highlighter = Highlighter::new(Vec::new(), vec![Regex::new("^hello$").unwrap()]);
+        assert!(matches(&highlighter, "\x02al[ex]\x02: hi", CaseMapping::Ascii));
+        assert!(matches(&highlighter, "\x0304al\x03[ex]", CaseMapping::Ascii));
+        assert!(matches(&highlighter, "\x1Dhello\x0F", CaseMapping::Ascii));
+    }
+}
@@ -0,0 +1,206 @@
+use std::collections::VecDeque;
+use std::fs;
+use std::fs::{File, OpenOptions};
+use std::io::{BufRead, BufReader, ErrorKind, Write};
+use std::path::{Path, PathBuf};
+
+use regex::Regex;
+
+use error;
+use model::BufferId;
+
+/// The default number of lines kept in the input history.
+pub const DEFAULT_HISTORY_SIZE: usize = 1000;
+
+/// The lines sent from the input line, along with the buffer that each was sent in. The history
+/// is kept in a file, one line per entry, so that it survives restarts.
+pub struct History {
+    path: Option<PathBuf>,
+    entries: VecDeque<(BufferId, String)>,
+    capacity: usize,
+    /// Lines that are never recorded, such as commands that carry a password.
+    exclude: Vec<Regex>,
+}
+
+impl History {
+    /// Creates an empty history that is not saved to disk.
+    pub fn new(capacity: usize, exclude: Vec<Regex>) -> History {
+        History {
+            path: None,
+            entries: VecDeque::new(),
+            capacity: capacity,
+            exclude: exclude,
+        }
+    }
+
+    /// Loads the history from the file at the specified path, which need not exist yet. Files
+    /// that have grown past the capacity are cut down to it.
+    pub fn load<P: AsRef<Path>>(
+        path: P, capacity: usize, exclude: Vec<Regex>
+    ) -> error::Result<History> {
+        let path = path.as_ref();
+        let read_error = |e| error::Error::HistoryRead {
+            path: path.display().to_string(),
+            cause: e,
+        };
+
+        let mut history = History::new(capacity, exclude);
+        history.path = Some(path.to_owned());
+        let file = match File::open(path) {
+            Ok(file) => file,
+            Err(ref e) if e.kind() == ErrorKind::NotFound => return Ok(history),
+            Err(e) => return Err(read_error(e)),
+        };
+
+        let mut total = 0;
+        for line in BufReader::new(file).lines() {
+            if let Some(entry) = parse_entry(&line.map_err(read_error)?) {
+                history.add(entry);
+                total += 1;
+            }
+        }
+        if total > history.entries.len() {
+            history.save()?;
+        }
+        Ok(history)
+    }
+
+    /// Adds a line sent in the buffer, appending it to the history file, unless it is excluded.
+    pub fn push(&mut self, buf: &BufferId, line: &str) -> error::Result<()> {
+        if self.capacity == 0 || self.exclude.iter().any(|pattern| pattern.is_match(line)) {
+            return Ok(());
+        }
+        self.add((buf.clone(), line.to_owned()));
+
+        let path = match self.path {
+            Some(ref path) => path,
+            None => return Ok(()),
+        };
+        let write_error = |e| error::Error::HistoryWrite {
+            path: path.display().to_string(),
+            cause: e,
+        };
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir).map_err(write_error)?;
+        }
+        let mut file = OpenOptions::new().create(true).append(true).open(path).map_err(
+            write_error
+        )?;
+        writeln!(file, "{}", format_entry(buf, line)).map_err(write_error)
+    }
+
+    /// The lines sent in the buffer, or in every buffer if none is given, oldest first.
+    pub fn lines(&self, buf: Option<&BufferId>) -> Vec<String> {
+        self.entries.iter().filter(|&&(ref id, _)| buf.is_none_or(|buf| buf == id)).map(
+            |&(_, ref line)| line.clone()
+        ).collect()
+    }
+
+    fn add(&mut self, entry: (BufferId, String)) {
+        if self.capacity == 0 {
+            return;
+        }
+        if self.entries.len() == self.capacity {
+            self.entries.pop_front();
+        }
+        self.entries.push_back(entry);
+    }
+
+    /// Writes out the whole history, replacing the file.
+    fn save(&self) -> error::Result<()> {
+        let path = match self.path {
+            Some(ref path) => path,
+            None => return Ok(()),
+        };
+        let write_error = |e| error::Error::HistoryWrite {
+            path: path.display().to_string(),
+            cause: e,
+        };
+        let mut file = File::create(path).map_err(write_error)?;
+        for &(ref buf, ref line) in &self.entries {
+            writeln!(file, "{}", format_entry(buf, line)).map_err(write_error)?;
+        }
+        Ok(())
+    }
+}
+
+/// Formats an entry as `server/buffer<TAB>line`, escaping the newlines of messages of several
+/// lines so that each entry takes a single line of the file.
+fn format_entry(buf: &BufferId, line: &str) -> String {
+    format!("{}\t{}", buf, line.replace('\\', "\\\\").replace('\n', "\\n"))
+}
+
+fn parse_entry(entry: &str) -> Option<(BufferId, String)> {
+    let mut parts = entry.splitn(2, '\t');
+    let (id, escaped) = (parts.next()?, parts.next()?);
+    let mut ids = id.splitn(2, '/');
+    let buf = BufferId::new(ids.next()?, ids.next()?);
+
+    let mut line = String::with_capacity(escaped.len());
+    let mut chars = escaped.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            line.push(c);
+            continue;
+        }
+        line.push(match chars.next() {
+            Some('n') => '\n',
+            Some(c) => c,
+            None => '\\',
+        });
+    }
+    Some((buf, line))
+}
+
+#[cfg(test)]
+mod tests {
+    use regex::Regex;
+
+    use model::BufferId;
+    use super::{format_entry, parse_entry, History};
+
+    fn chan() -> BufferId {
+        BufferId::new("irc.example.net", "#chan")
+    }
+
+    #[test]
+    fn format_entries() {
+        assert_eq!(format_entry(&chan(), "hello"), "irc.example.net/#chan\thello");
+        assert_eq!(format_entry(&chan(), "one\ntwo"), "irc.example.net/#chan\tone\\ntwo");
+        // Backslashes are escaped, so they cannot be mistaken for a newline.
+        assert_eq!(format_entry(&chan(), "a\\nb"), "irc.example.net/#chan\ta\\\\nb");
+    }
+
+    #[test]
+    fn parse_entries() {
+        let parse = |entry| parse_entry(entry).unwrap();
+        assert_eq!(parse("irc.example.net/#chan\thello"), (chan(), "hello".to_owned()));
+        assert_eq!(parse("irc.example.net/#chan\tone\\ntwo"), (chan(), "one\ntwo".to_owned()));
+        assert_eq!(parse("irc.example.net/#chan\ta\\\\nb"), (chan(), "a\\nb".to_owned()));
+        assert_eq!(parse("irc.example.net/#chan\ttabs\tkept"), (chan(), "tabs\tkept".to_owned()));
+        assert_eq!(parse("irc.example.net/#chan\tdangling\\"), (chan(), "dangling\\".to_owned()));
+    }
+
+    #[test]
+    fn entries_of_several_lines() {
+        for line in &["", "\n\nthree lines\n", "trailing\\", "\\n\n\\\n"] {
+            let entry = format_entry(&chan(), line);
+            assert!(!entry.contains('\n'));
+            assert_eq!(parse_entry(&entry), Some((chan(), line.to_string())));
+        }
+    }
+
+    #[test]
+    fn malformed_entries() {
+        assert_eq!(parse_entry("no tab"), None);
+        assert_eq!(parse_entry("#chan\tno server"), None);
+    }
+
+    #[test]
+    fn excluded_lines() {
+        let mut history = History::new(10, vec![Regex::new("(?i)^/msg nickserv ").unwrap()]);
+        history.push(&chan(), "/msg NickServ IDENTIFY secret").unwrap();
+        history.push(&chan(), "/msg nick hi").unwrap();
+        assert_eq!(history.lines(None), vec!["/msg nick hi".to_owned()]);
+    }
+}
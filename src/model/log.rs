@@ -0,0 +1,412 @@
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::fs::{File, OpenOptions};
+use std::io::{BufRead, BufReader, ErrorKind, Write};
+use std::path::{Path, PathBuf};
+
+use chrono::{DateTime, Local, NaiveDate};
+
+use error;
+use model::{BufferId, Event, EventKind};
+use utils;
+
+/// The format of the timestamp that starts each line of a log, RFC 3339 to the second.
+const TIME_FORMAT: &str = "%Y-%m-%dT%H:%M:%S%:z";
+
+/// Writes the events of each buffer to a file per day, at `<dir>/<network>/<buffer>/<date>.log`.
+///
+/// Each line of a log is an event, written as its timestamp followed by its kind and fields,
+/// separated by tabs.
+pub struct Logger {
+    dir: PathBuf,
+    strip_formatting: bool,
+    /// The buffers not to log, by name or by `network/name`.
+    exclude: Vec<String>,
+    /// Buffers whose logs could not be written, which are no longer logged.
+    disabled: HashSet<BufferId>,
+    /// The open log of each buffer, along with the day that it is for.
+    files: HashMap<BufferId, (NaiveDate, File)>,
+}
+
+impl Logger {
+    pub fn new<P: AsRef<Path>>(dir: P, strip_formatting: bool, exclude: Vec<String>) -> Logger {
+        Logger {
+            dir: dir.as_ref().to_owned(),
+            strip_formatting: strip_formatting,
+            exclude: exclude,
+            disabled: HashSet::new(),
+            files: HashMap::new(),
+        }
+    }
+
+    /// Whether events in the buffer are logged.
+    pub fn is_logged(&self, buf: &BufferId) -> bool {
+        let qualified = buf.to_string();
+        !self.disabled.contains(buf) && !self.exclude.iter().any(|pattern| {
+            pattern.eq_ignore_ascii_case(&buf.name) || pattern.eq_ignore_ascii_case(&qualified)
+        })
+    }
+
+    /// Appends the event to the buffer's log for the day on which it occurred.
+    pub fn log(&mut self, buf: &BufferId, event: &Event) -> error::Result<()> {
+        let line = match format_event(event, self.strip_formatting) {
+            Some(line) => line,
+            None => return Ok(()),
+        };
+        if !self.is_logged(buf) {
+            return Ok(());
+        }
+
+        let date = event.time.naive_local().date();
+        let path = self.path(buf, date);
+        let write_error = |e| error::Error::LogWrite {
+            path: path.display().to_string(),
+            cause: e,
+        };
+
+        if let Some(&mut (day, ref mut file)) = self.files.get_mut(buf) {
+            if day == date {
+                return writeln!(file, "{}", line).map_err(write_error);
+            }
+        }
+
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir).map_err(write_error)?;
+        }
+        let mut file = OpenOptions::new().create(true).append(true).open(&path).map_err(
+            write_error
+        )?;
+        writeln!(file, "{}", line).map_err(write_error)?;
+        self.files.insert(buf.clone(), (date, file));
+        Ok(())
+    }
+
+    /// Reads back up to the specified number of the most recent events from the buffer's logs,
+    /// oldest first. Lines that cannot be parsed are skipped.
+    pub fn backlog(&self, buf: &BufferId, count: usize) -> error::Result<Vec<Event>> {
+        if count == 0 || !self.is_logged(buf) {
+            return Ok(Vec::new());
+        }
+
+        let dir = self.dir(buf);
+        let read_error = |path: &Path, e| error::Error::LogRead {
+            path: path.display().to_string(),
+            cause: e,
+        };
+
+        let entries = match fs::read_dir(&dir) {
+            Ok(entries) => entries,
+            Err(ref e) if e.kind() == ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(e) => return Err(read_error(&dir, e)),
+        };
+        let mut logs = Vec::new();
+        for entry in entries {
+            let path = entry.map_err(|e| read_error(&dir, e))?.path();
+            if path.extension().is_some_and(|ext| ext == "log") {
+                logs.push(path);
+            }
+        }
+        // The logs are named by date, so they sort by name from the most recent.
+        logs.sort_by(|a, b| b.cmp(a));
+
+        let mut backlog = Vec::new();
+        for path in logs {
+            let file = File::open(&path).map_err(|e| read_error(&path, e))?;
+            let mut events = Vec::new();
+            for line in BufReader::new(file).lines() {
+                events.extend(parse_event(&line.map_err(|e| read_error(&path, e))?));
+            }
+            let rest = count - backlog.len();
+            backlog.extend(events.into_iter().rev().take(rest));
+            if backlog.len() == count {
+                break;
+            }
+        }
+        backlog.reverse();
+        Ok(backlog)
+    }
+
+    /// Stops logging the buffer, e.g. after its log could not be written.
+    pub fn disable(&mut self, buf: &BufferId) {
+        self.files.remove(buf);
+        self.disabled.insert(buf.clone());
+    }
+
+    /// Closes the buffer's log, which is opened again by the next event in it.
+    pub fn close(&mut self, buf: &BufferId) {
+        self.files.remove(buf);
+    }
+
+    /// The log of the buffer for the specified day.
+    pub fn path(&self, buf: &BufferId, date: NaiveDate) -> PathBuf {
+        self.dir(buf).join(format!("{}.log", date.format("%Y-%m-%d")))
+    }
+
+    /// The directory that holds the logs of the buffer.
+    fn dir(&self, buf: &BufferId) -> PathBuf {
+        self.dir.join(path_component(&buf.server)).join(path_component(&buf.name))
+    }
+}
+
+/// Makes a network or buffer name safe to use as a directory name. Names are lowercased, as IRC
+/// compares channel names without regard to case.
+fn path_component(name: &str) -> String {
+    let name = name.to_lowercase().replace(['/', '\0'], "_");
+    if name.is_empty() || name.chars().all(|c| c == '.') {
+        format!("_{}", name)
+    } else {
+        name
+    }
+}
+
+/// Formats an event as a line of a log. A missing sender is written as an empty field, and a
+/// missing reason is left out. Separators are not logged, nor are mentions, which repeat events
+/// logged in other buffers.
+fn format_event(event: &Event, strip_formatting: bool) -> Option<String> {
+    let text = |s: &str| if strip_formatting { utils::strip_formatting(s) } else { s.to_owned() };
+    let sender = |s: &Option<String>| s.clone().unwrap_or_default();
+
+    let (kind, fields) = match &event.kind {
+        &EventKind::PrivMessage(ref s, ref target, ref message) => {
+            ("message", vec![sender(s), target.clone(), text(message)])
+        }
+        &EventKind::Notice(ref s, ref target, ref message) => {
+            ("notice", vec![sender(s), target.clone(), text(message)])
+        }
+        &EventKind::JoinPart(ref s, ref target, joined) => {
+            (if joined { "join" } else { "part" }, vec![sender(s), target.clone()])
+        }
+        &EventKind::Topic(ref s, ref target, ref topic) => {
+            ("topic", vec![sender(s), target.clone(), text(topic)])
+        }
+        &EventKind::Quit(ref s, ref target, ref reason) => {
+            let mut fields = vec![sender(s), target.clone()];
+            fields.extend(reason.as_ref().map(|reason| text(reason)));
+            ("quit", fields)
+        }
+        &EventKind::Nick(ref s, ref target, ref new) => {
+            ("nick", vec![sender(s), target.clone(), new.clone()])
+        }
+        &EventKind::Kick(ref s, ref target, ref kicked, ref reason) => {
+            let mut fields = vec![sender(s), target.clone(), kicked.clone()];
+            fields.extend(reason.as_ref().map(|reason| text(reason)));
+            ("kick", fields)
+        }
+        &EventKind::Mode(ref s, ref target, ref modes) => {
+            ("mode", vec![sender(s), target.clone(), modes.clone()])
+        }
+        &EventKind::Invite(ref s, ref target, ref channel) => {
+            ("invite", vec![sender(s), target.clone(), channel.clone()])
+        }
+        &EventKind::Action(ref s, ref target, ref action) => {
+            ("action", vec![sender(s), target.clone(), text(action)])
+        }
+        &EventKind::Error(ref target, ref message) => {
+            ("error", vec![target.clone(), message.clone()])
+        }
+        &EventKind::Raw(ref target, outgoing, ref line) => {
+            let line = line.trim_end_matches("\r\n").to_owned();
+            (if outgoing { "sent" } else { "received" }, vec![target.clone(), line])
+        }
+        &EventKind::Separator(_, _) | &EventKind::Mention(_, _) => return None,
+    };
+
+    Some(fields.iter().fold(
+        format!("{}\t{}", event.time.format(TIME_FORMAT), kind),
+        |line, field| format!("{}\t{}", line, escape(field))
+    ))
+}
+
+/// Parses a line of a log back into the event that it was written from.
+fn parse_event(line: &str) -> Option<Event> {
+    let mut fields = line.split('\t');
+    let time = DateTime::parse_from_rfc3339(fields.next()?).ok()?.with_timezone(&Local);
+    let kind = fields.next()?;
+    let fields: Vec<String> = fields.map(unescape).collect();
+    let fields: Vec<&str> = fields.iter().map(|field| &field[..]).collect();
+
+    let event = match (kind, &fields[..]) {
+        ("message", &[s, target, message]) => Event::message(sender(s), target, message),
+        ("notice", &[s, target, message]) => Event::notice(sender(s), target, message),
+        ("join", &[s, target]) => Event::joined(sender(s), target),
+        ("part", &[s, target]) => Event::parted(sender(s), target),
+        ("topic", &[s, target, topic]) => Event::topic(sender(s), target, topic),
+        ("quit", &[s, target]) => Event::quit(sender(s), target, None),
+        ("quit", &[s, target, reason]) => Event::quit(sender(s), target, Some(reason)),
+        ("nick", &[s, target, new]) => Event::nick(sender(s), target, new),
+        ("kick", &[s, target, kicked]) => Event::kick(sender(s), target, kicked, None),
+        ("kick", &[s, target, kicked, reason]) => {
+            Event::kick(sender(s), target, kicked, Some(reason))
+        }
+        ("mode", &[s, target, modes]) => Event::mode(sender(s), target, modes),
+        ("invite", &[s, target, channel]) => Event::invite(sender(s), target, channel),
+        ("action", &[s, target, action]) => Event::action(sender(s), target, action),
+        ("error", &[target, message]) => Event::error(target, message),
+        ("sent", &[target, line]) => Event::raw(target, true, line),
+        ("received", &[target, line]) => Event::raw(target, false, line),
+        _ => return None,
+    };
+    Some(event.at(time))
+}
+
+/// The sender of a logged event, which is written as an empty field when there is none.
+fn sender(field: &str) -> Option<&str> {
+    if field.is_empty() { None } else { Some(field) }
+}
+
+/// Escapes the characters that would break a field across lines or into several fields.
+fn escape(field: &str) -> String {
+    field.replace('\\', "\\\\").replace('\t', "\\t").replace('\r', "\\r").replace('\n', "\\n")
+}
+
+fn unescape(field: &str) -> String {
+    let mut unescaped = String::with_capacity(field.len());
+    let mut chars = field.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            unescaped.push(c);
+            continue;
+        }
+        unescaped.push(match chars.next() {
+            Some('t') => '\t',
+            Some('r') => '\r',
+            Some('n') => '\n',
+            Some(c) => c,
+            None => '\\',
+        });
+    }
+    unescaped
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{DateTime, FixedOffset, Local};
+
+    use model::{Event, EventKind};
+    use super::{format_event, parse_event};
+
+    fn time() -> DateTime<FixedOffset> {
+        DateTime::parse_from_rfc3339("2018-06-01T12:30:05+02:00").unwrap()
+    }
+
+    /// Formats the event as it would be logged at a fixed time, split into its fields.
+    fn fields(event: Event, strip_formatting: bool) -> Vec<String> {
+        let line = format_event(&event.at(time().with_timezone(&Local)), strip_formatting).unwrap();
+        assert!(!line.contains('\n') && !line.contains('\r'));
+        line.split('\t').map(|field| field.to_owned()).collect()
+    }
+
+    /// Formats the event, checks that it parses back into an event that formats the same, and
+    /// returns the parsed event.
+    fn round_trip(event: Event, strip_formatting: bool) -> Event {
+        let event = event.at(time().with_timezone(&Local));
+        let line = format_event(&event, strip_formatting).unwrap();
+        let parsed = parse_event(&line).expect(&line);
+        assert_eq!(parsed.time, time());
+        assert_eq!(format_event(&parsed, strip_formatting), Some(line));
+        parsed
+    }
+
+    #[test]
+    fn format_every_kind() {
+        let events = vec![
+            (vec!["message", "nick", "#chan", "hi"], Event::message(Some("nick"), "#chan", "hi")),
+            (vec!["notice", "", "nick", "hi"], Event::notice(None, "nick", "hi")),
+            (vec!["join", "nick", "#chan"], Event::joined(Some("nick"), "#chan")),
+            (vec!["part", "nick", "#chan"], Event::parted(Some("nick"), "#chan")),
+            (vec!["topic", "nick", "#chan", "topic"], Event::topic(Some("nick"), "#chan", "topic")),
+            (vec!["quit", "nick", "#chan"], Event::quit(Some("nick"), "#chan", None)),
+            (vec!["quit", "nick", "#chan", "bye"], Event::quit(Some("nick"), "#chan", Some("bye"))),
+            (vec!["nick", "nick", "#chan", "new"], Event::nick(Some("nick"), "#chan", "new")),
+            (vec!["kick", "op", "#chan", "nick"], Event::kick(Some("op"), "#chan", "nick", None)),
+            (
+                vec!["kick", "op", "#chan", "nick", "spam"],
+                Event::kick(Some("op"), "#chan", "nick", Some("spam")),
+            ),
+            (vec!["mode", "op", "#chan", "+o nick"], Event::mode(Some("op"), "#chan", "+o nick")),
+            (vec!["invite", "nick", "me", "#chan"], Event::invite(Some("nick"), "me", "#chan")),
+            (vec!["action", "nick", "#chan", "wave"], Event::action(Some("nick"), "#chan", "wave")),
+            (vec!["error", "#chan", "failed"], Event::error("#chan", "failed")),
+            (vec!["sent", "*raw*", "QUIT"], Event::raw("*raw*", true, "QUIT\r\n")),
+            (vec!["received", "*raw*", "PING :x"], Event::raw("*raw*", false, "PING :x\r\n")),
+        ];
+        for (expected, event) in events {
+            round_trip(event.clone(), true);
+            let fields = fields(event, true);
+            assert_eq!(DateTime::parse_from_rfc3339(&fields[0]), Ok(time()));
+            assert_eq!(&fields[1..], &expected[..]);
+        }
+    }
+
+    #[test]
+    fn format_escapes() {
+        let fields = fields(Event::message(Some("nick"), "#chan", "a\tb\nc\r\\d"), true);
+        assert_eq!(fields.len(), 5);
+        assert_eq!(fields[4], "a\\tb\\nc\\r\\\\d");
+    }
+
+    #[test]
+    fn format_formatting() {
+        let message = "\x02bold\x02 \x0304,01red\x03 \x1Ditalic\x0F";
+        assert_eq!(fields(Event::message(None, "#chan", message), true)[4], "bold red italic");
+        assert_eq!(fields(Event::message(None, "#chan", message), false)[4], message);
+    }
+
+    #[test]
+    fn round_trip_fields() {
+        match round_trip(Event::kick(None, "#chan", "nick", Some("spam")), true).kind {
+            EventKind::Kick(None, ref target, ref kicked, Some(ref reason)) => {
+                assert_eq!((&target[..], &kicked[..], &reason[..]), ("#chan", "nick", "spam"));
+            }
+            _ => panic!("expected a kick with a reason and no sender"),
+        }
+        match round_trip(Event::raw("*raw*", true, "QUIT\r\n"), true).kind {
+            EventKind::Raw(_, true, ref line) => assert_eq!(line, "QUIT"),
+            _ => panic!("expected a sent line"),
+        }
+    }
+
+    #[test]
+    fn round_trip_escapes() {
+        let message = "a\tb\nc\r\\d \\t\\";
+        match round_trip(Event::message(Some("nick"), "#chan", message), true).kind {
+            EventKind::PrivMessage(_, _, ref parsed) => assert_eq!(parsed, message),
+            _ => panic!("expected a message"),
+        }
+    }
+
+    #[test]
+    fn round_trip_formatting() {
+        let message = "\x02bold\x02 \x0304,01red\x03 \x1Ditalic\x0F";
+        match round_trip(Event::message(Some("nick"), "#chan", message), false).kind {
+            EventKind::PrivMessage(_, _, ref parsed) => assert_eq!(parsed, message),
+            _ => panic!("expected a message"),
+        }
+    }
+
+    #[test]
+    fn unlogged_kinds() {
+        let message = Event::message(Some("nick"), "#chan", "me: hi");
+        assert!(format_event(&Event::separator("#chan", "End of backlog"), true).is_none());
+        assert!(format_event(&Event::mention("#chan", message), true).is_none());
+    }
+
+    #[test]
+    fn malformed_lines() {
+        let lines = [
+            "",
+            "not a log line",
+            "yesterday\tmessage\tnick\t#chan\thello",
+            "2018-06-01T12:30:05+02:00",
+            "2018-06-01T12:30:05+02:00\tmessage\tnick\t#chan",
+            "2018-06-01T12:30:05+02:00\tmessage\tnick\t#chan\thello\textra",
+            "2018-06-01T12:30:05+02:00\tjoin\tnick",
+            "2018-06-01T12:30:05+02:00\tkick\top\t#chan",
+            "2018-06-01T12:30:05+02:00\tseparator\t#chan\tEnd of backlog",
+            "2018-06-01T12:30:05+02:00\tunknown\tnick\t#chan",
+        ];
+        for line in &lines {
+            assert!(parse_event(line).is_none(), "parsed {:?}", line);
+        }
+    }
+}
@@ -0,0 +1,226 @@
+use std::slice::Iter;
+
+use irc::client::data::{AccessLevel, User};
+use irc::proto::{ChannelMode, Mode};
+
+use model::CaseMapping;
+
+/// A single member of a channel along with their access levels.
+#[derive(Clone)]
+pub struct Member {
+    nickname: String,
+    access_levels: Vec<AccessLevel>,
+}
+
+impl Member {
+    /// Creates a member from a nickname, parsing off any access level prefixes (e.g. `@aatxe`).
+    pub fn new(user: &str) -> Member {
+        let user = User::new(user);
+        Member {
+            nickname: user.get_nickname().to_owned(),
+            access_levels: user.access_levels(),
+        }
+    }
+
+    pub fn nickname(&self) -> &str {
+        &self.nickname
+    }
+
+    pub fn highest_access_level(&self) -> AccessLevel {
+        self.access_levels.iter().min_by_key(|level| rank(level)).cloned().unwrap_or(
+            AccessLevel::Member
+        )
+    }
+
+    /// The conventional prefix for the member's highest access level.
+    pub fn prefix(&self) -> &'static str {
+        match self.highest_access_level() {
+            AccessLevel::Owner => "~",
+            AccessLevel::Admin => "&",
+            AccessLevel::Oper => "@",
+            AccessLevel::HalfOp => "%",
+            AccessLevel::Voice => "+",
+            AccessLevel::Member => "",
+        }
+    }
+
+    fn update_access_level(&mut self, mode: &Mode<ChannelMode>) {
+        match mode {
+            &Mode::Plus(ref mode, _) => if let Some(level) = access_level(mode) {
+                if !self.access_levels.contains(&level) {
+                    self.access_levels.push(level);
+                }
+            },
+            &Mode::Minus(ref mode, _) => if let Some(level) = access_level(mode) {
+                self.access_levels.retain(|l| *l != level);
+            },
+        }
+    }
+}
+
+/// The members of a channel, sorted by access level and then by nickname.
+#[derive(Clone, Default)]
+pub struct MemberList {
+    members: Vec<Member>,
+}
+
+impl MemberList {
+    pub fn new() -> MemberList {
+        MemberList::default()
+    }
+
+    /// Adds a member to the list, replacing any existing member with the same nickname. Here and
+    /// below, nicknames are compared under the network's case mapping.
+    pub fn add(&mut self, user: &str, casemapping: CaseMapping) {
+        let member = Member::new(user);
+        let nickname = casemapping.to_lower(&member.nickname);
+        self.members.retain(|m| casemapping.to_lower(&m.nickname) != nickname);
+        self.members.push(member);
+        self.sort();
+    }
+
+    /// Removes the member with the given nickname, returning whether they were in the list.
+    pub fn remove(&mut self, nickname: &str, casemapping: CaseMapping) -> bool {
+        let nickname = casemapping.to_lower(nickname);
+        let len = self.members.len();
+        self.members.retain(|m| casemapping.to_lower(&m.nickname) != nickname);
+        self.members.len() != len
+    }
+
+    /// Renames the member with the given nickname, returning whether they were in the list.
+    pub fn rename(&mut self, old: &str, new: &str, casemapping: CaseMapping) -> bool {
+        let renamed = match self.find(old, casemapping) {
+            Some(member) => {
+                member.nickname = new.to_owned();
+                true
+            }
+            None => false,
+        };
+        self.sort();
+        renamed
+    }
+
+    /// Applies a channel mode change, updating the access levels of the affected member.
+    pub fn update_mode(&mut self, mode: &Mode<ChannelMode>, casemapping: CaseMapping) {
+        let nickname = match mode {
+            &Mode::Plus(_, Some(ref nickname)) | &Mode::Minus(_, Some(ref nickname)) => nickname,
+            _ => return,
+        };
+        if let Some(member) = self.find(nickname, casemapping) {
+            member.update_access_level(mode);
+        }
+        self.sort();
+    }
+
+    pub fn contains(&self, nickname: &str, casemapping: CaseMapping) -> bool {
+        let nickname = casemapping.to_lower(nickname);
+        self.members.iter().any(|m| casemapping.to_lower(&m.nickname) == nickname)
+    }
+
+    pub fn iter(&self) -> Iter<Member> {
+        self.members.iter()
+    }
+
+    pub fn len(&self) -> usize {
+        self.members.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.members.is_empty()
+    }
+
+    pub fn clear(&mut self) {
+        self.members.clear()
+    }
+
+    fn find(&mut self, nickname: &str, casemapping: CaseMapping) -> Option<&mut Member> {
+        let nickname = casemapping.to_lower(nickname);
+        self.members.iter_mut().find(|m| casemapping.to_lower(&m.nickname) == nickname)
+    }
+
+    fn sort(&mut self) {
+        self.members.sort_by(|a, b| {
+            rank(&a.highest_access_level()).cmp(&rank(&b.highest_access_level())).then_with(||
+                a.nickname.to_lowercase().cmp(&b.nickname.to_lowercase())
+            )
+        })
+    }
+}
+
+impl<'a> IntoIterator for &'a MemberList {
+    type Item = &'a Member;
+    type IntoIter = Iter<'a, Member>;
+
+    fn into_iter(self) -> Iter<'a, Member> {
+        self.members.iter()
+    }
+}
+
+/// Ranks access levels from highest (zero) to lowest.
+fn rank(level: &AccessLevel) -> u8 {
+    match level {
+        &AccessLevel::Owner => 0,
+        &AccessLevel::Admin => 1,
+        &AccessLevel::Oper => 2,
+        &AccessLevel::HalfOp => 3,
+        &AccessLevel::Voice => 4,
+        &AccessLevel::Member => 5,
+    }
+}
+
+/// Determines the access level granted by a channel mode, if any.
+fn access_level(mode: &ChannelMode) -> Option<AccessLevel> {
+    match mode {
+        &ChannelMode::Founder => Some(AccessLevel::Owner),
+        &ChannelMode::Admin => Some(AccessLevel::Admin),
+        &ChannelMode::Oper => Some(AccessLevel::Oper),
+        &ChannelMode::Halfop => Some(AccessLevel::HalfOp),
+        &ChannelMode::Voice => Some(AccessLevel::Voice),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use irc::client::data::AccessLevel;
+    use irc::proto::{ChannelMode, Mode};
+
+    use model::CaseMapping;
+    use super::MemberList;
+
+    fn members(users: &[&str]) -> MemberList {
+        let mut members = MemberList::new();
+        for user in users {
+            members.add(user, CaseMapping::Rfc1459);
+        }
+        members
+    }
+
+    fn nicknames(members: &MemberList) -> Vec<String> {
+        members.iter().map(|member| format!("{}{}", member.prefix(), member.nickname())).collect()
+    }
+
+    #[test]
+    fn sorted_by_access_level_then_nickname() {
+        let members = members(&["bob", "+Carol", "@dave", "alice", "~erin"]);
+        assert_eq!(nicknames(&members), vec!["~erin", "@dave", "+Carol", "alice", "bob"]);
+    }
+
+    #[test]
+    fn nicknames_compared_under_the_casemapping() {
+        let mut members = members(&["Al[ex]", "bob"]);
+        members.add("@al{ex}", CaseMapping::Rfc1459);
+        assert_eq!(nicknames(&members), vec!["@al{ex}", "bob"]);
+
+        assert!(members.rename("BOB", "Robert", CaseMapping::Rfc1459));
+        assert!(members.contains("robert", CaseMapping::Rfc1459));
+        let deop = Mode::Minus(ChannelMode::Oper, Some("AL[EX]".to_owned()));
+        members.update_mode(&deop, CaseMapping::Rfc1459);
+        assert_eq!(members.iter().next().unwrap().highest_access_level(), AccessLevel::Member);
+
+        // Under ascii, brackets and braces are different characters.
+        assert!(!members.remove("al[ex]", CaseMapping::Ascii));
+        assert!(members.remove("AL[EX]", CaseMapping::Rfc1459));
+        assert_eq!(nicknames(&members), vec!["Robert"]);
+    }
+}
@@ -1,6 +1,20 @@
+mod activity;
 mod application;
+mod buffer;
 mod chatbuf;
 mod event;
+mod highlight;
+mod history;
+mod log;
+mod members;
+mod topic;
 
-pub use self::chatbuf::ChatBuf;
-pub use self::event::Event;
+pub use self::activity::{Activity, ActivityLevel};
+pub use self::buffer::{BufferId, HIGHLIGHTS, RAW, STATUS};
+pub use self::chatbuf::{ChatBuf, DEFAULT_CAPACITY};
+pub use self::event::{Event, EventKind};
+pub use self::highlight::{CaseMapping, Highlighter};
+pub use self::history::{DEFAULT_HISTORY_SIZE, History};
+pub use self::log::Logger;
+pub use self::members::{Member, MemberList};
+pub use self::topic::Topic;
@@ -0,0 +1,15 @@
+/// The topic of a channel, along with who set it if known.
+#[derive(Clone, Default)]
+pub struct Topic {
+    pub text: String,
+    pub set_by: Option<String>,
+}
+
+impl Topic {
+    pub fn new(text: &str) -> Topic {
+        Topic {
+            text: text.to_owned(),
+            set_by: None,
+        }
+    }
+}
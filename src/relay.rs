@@ -0,0 +1,145 @@
+use std::fs::File;
+use std::io::Read;
+use std::net::{SocketAddr, ToSocketAddrs};
+
+use futures::future::{self, Either, Loop};
+use futures::{Future, Stream};
+use irc::client::prelude::Config;
+use native_tls::{Certificate, TlsConnector};
+use tokio_core::net::{TcpListener, TcpStream};
+use tokio_core::reactor::Handle;
+use tokio_io::{io, AsyncRead, AsyncWrite};
+use tokio_tls::TlsConnectorExt;
+
+use error;
+use model::{BufferId, Event};
+use view::UI;
+
+/// The most bytes read from either side of a connection at once.
+const READ_LEN: usize = 4096;
+
+/// A connection relayed between the irc client and a network, which ends when either side
+/// closes it.
+pub type Relay = Box<dyn Future<Item = (), Error = error::Error>>;
+
+/// Listens locally for the irc client's connection to the network of the config, which is relayed
+/// so that the network's raw buffer shows every line exactly as it is sent and received, including
+/// those that the client sends by itself. Returns the config for the client to connect with, along
+/// with the relay to run on the reactor.
+pub fn relay(
+    server: &str, config: &Config, ui: UI, handle: &Handle
+) -> error::Result<(Config, Relay)> {
+    let domain = config.server()?.to_owned();
+    let addr = (&*domain, config.port()).to_socket_addrs()?.next().ok_or_else(|| {
+        error::Error::ServerNotFound { server: domain.clone() }
+    })?;
+    let tls = if config.use_ssl() { Some(tls_connector(config)?) } else { None };
+
+    let listener = TcpListener::bind(&SocketAddr::from(([127, 0, 0, 1], 0)), handle)?;
+    let local_addr = listener.local_addr()?;
+    let local = Config {
+        server: Some(local_addr.ip().to_string()),
+        port: Some(local_addr.port()),
+        use_ssl: Some(false),
+        ..config.clone()
+    };
+
+    let raw = BufferId::raw(server);
+    let handle = handle.clone();
+    let relay = listener.incoming().into_future().map_err(|(e, _)| e.into()).and_then(
+        move |(client, _)| {
+            // Listeners never run out of connections to accept.
+            let (client, _) = client.unwrap();
+            let network = TcpStream::connect(&addr, &handle).map_err(error::Error::from);
+            match tls {
+                Some(tls) => Either::A(network.and_then(move |network| {
+                    tls.connect_async(&domain, network).map_err(error::Error::from)
+                }).and_then(move |network| splice(client, network, ui, raw))),
+                None => Either::B(network.and_then(move |network| {
+                    splice(client, network, ui, raw)
+                })),
+            }
+        }
+    );
+    Ok((local, Box::new(relay)))
+}
+
+/// Connects with TLS as the irc client would, trusting the config's certificate if it has one.
+fn tls_connector(config: &Config) -> error::Result<TlsConnector> {
+    let mut builder = TlsConnector::builder()?;
+    if let Some(ref path) = config.cert_path {
+        let mut der = Vec::new();
+        File::open(path)?.read_to_end(&mut der)?;
+        builder.add_root_certificate(Certificate::from_der(&der)?)?;
+    }
+    Ok(builder.build()?)
+}
+
+/// Copies between the client and the network until either closes the connection.
+fn splice<S>(client: TcpStream, network: S, ui: UI, raw: BufferId) -> Relay
+where S: AsyncRead + AsyncWrite + 'static {
+    let (client_rx, client_tx) = client.split();
+    let (network_rx, network_tx) = network.split();
+    let sent = copy(client_rx, network_tx, RawLog::new(ui.clone(), raw.clone(), true));
+    let received = copy(network_rx, client_tx, RawLog::new(ui, raw, false));
+    Box::new(sent.select(received).map(|_| ()).map_err(|(e, _)| e))
+}
+
+/// Copies everything read from one side to the other, until the end of the input.
+fn copy<R, W>(from: R, to: W, log: RawLog) -> Relay
+where R: AsyncRead + 'static, W: AsyncWrite + 'static {
+    Box::new(future::loop_fn((from, to, log), |(from, to, mut log)| {
+        io::read(from, vec![0; READ_LEN]).map_err(error::Error::from).and_then(
+            move |(from, mut bytes, len)| {
+                if len == 0 {
+                    return Either::A(future::ok(Loop::Break(())));
+                }
+                bytes.truncate(len);
+                Either::B(future::result(log.push(&bytes)).and_then(move |()| {
+                    io::write_all(to, bytes).map_err(error::Error::from)
+                }).map(move |(to, _)| Loop::Continue((from, to, log))))
+            }
+        )
+    }))
+}
+
+/// Shows the lines passing one way through a relay in the network's raw buffer, while it is open.
+struct RawLog {
+    ui: UI,
+    raw: BufferId,
+    outgoing: bool,
+    /// The start of a line whose end has not been read yet.
+    partial: Vec<u8>,
+}
+
+impl RawLog {
+    fn new(ui: UI, raw: BufferId, outgoing: bool) -> RawLog {
+        RawLog {
+            ui: ui,
+            raw: raw,
+            outgoing: outgoing,
+            partial: Vec::new(),
+        }
+    }
+
+    /// Shows each line that the bytes complete.
+    fn push(&mut self, bytes: &[u8]) -> error::Result<()> {
+        self.partial.extend_from_slice(bytes);
+        let mut shown = false;
+        while let Some(end) = self.partial.iter().position(|&b| b == b'\n') {
+            let line: Vec<u8> = self.partial.drain(..end + 1).collect();
+            if self.ui.has_chat_buf(&self.raw)? {
+                let line = String::from_utf8_lossy(&line[..end]);
+                let event = Event::raw(&self.raw.name, self.outgoing, line.trim_end_matches('\r'));
+                self.ui.add_event_to_chat_buf(&self.raw, event)?;
+                shown = true;
+            }
+        }
+
+        // Lines sent by the client itself are not followed by a redraw otherwise.
+        if shown {
+            self.ui.draw_all()?;
+        }
+        Ok(())
+    }
+}
@@ -1,3 +1,5 @@
 mod colorize;
+mod text;
 
 pub use self::colorize::colorize;
+pub use self::text::{split_bytes, strip_formatting, truncate};
@@ -0,0 +1,134 @@
+use unicode_segmentation::UnicodeSegmentation;
+use unicode_width::UnicodeWidthStr;
+
+/// Removes IRC formatting codes (bold, italics, underline, colors, etc.) from the string.
+pub fn strip_formatting(s: &str) -> String {
+    let mut stripped = String::with_capacity(s.len());
+    let mut chars = s.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '\x02' | '\x1D' | '\x1F' | '\x16' | '\x0F' => (),
+            '\x03' => {
+                // Skip up to two digits of foreground color, and optionally a comma followed by
+                // up to two digits of background color.
+                for _ in 0..2 {
+                    if chars.peek().is_some_and(|c| c.is_ascii_digit()) {
+                        chars.next();
+                    }
+                }
+                if chars.peek() == Some(&',') {
+                    let mut lookahead = chars.clone();
+                    lookahead.next();
+                    if lookahead.peek().is_some_and(|c| c.is_ascii_digit()) {
+                        chars.next();
+                        for _ in 0..2 {
+                            if chars.peek().is_some_and(|c| c.is_ascii_digit()) {
+                                chars.next();
+                            }
+                        }
+                    }
+                }
+            }
+            c => stripped.push(c),
+        }
+    }
+
+    stripped
+}
+
+/// Truncates the string to fit within the given display width.
+pub fn truncate(s: &str, width: usize) -> String {
+    let mut truncated = String::new();
+    for g in UnicodeSegmentation::graphemes(s, true) {
+        if truncated.width() + g.width() > width {
+            break;
+        }
+        truncated.push_str(g);
+    }
+    truncated
+}
+
+/// Splits the string into pieces of at most `max` bytes, breaking at whitespace where possible and
+/// otherwise between graphemes. The whitespace at each break is dropped, and a string of only
+/// whitespace has no pieces.
+pub fn split_bytes(s: &str, max: usize) -> Vec<&str> {
+    let mut pieces = Vec::new();
+    let mut rest = s;
+    while rest.len() > max {
+        let mut end = 0;
+        let mut space = None;
+        for (i, g) in rest.grapheme_indices(true) {
+            if i + g.len() > max {
+                break;
+            }
+            if i > 0 && g.chars().all(char::is_whitespace) {
+                space = Some(i);
+            }
+            end = i + g.len();
+        }
+        if rest[end..].starts_with(char::is_whitespace) {
+            space = Some(end);
+        }
+
+        // A single grapheme longer than the limit is broken between its chars instead.
+        let split = match space {
+            Some(i) => i,
+            None if end > 0 => end,
+            None => (1..max + 1).rev().find(|&i| rest.is_char_boundary(i)).unwrap_or_else(|| {
+                rest.chars().next().map_or(rest.len(), char::len_utf8)
+            }),
+        };
+        let piece = rest[..split].trim_end();
+        if !piece.is_empty() {
+            pieces.push(piece);
+        }
+        rest = rest[split..].trim_start();
+    }
+    if !rest.trim().is_empty() {
+        pieces.push(rest);
+    }
+    pieces
+}
+
+#[cfg(test)]
+mod tests {
+    use super::split_bytes;
+
+    #[test]
+    fn split_bytes_fits() {
+        assert_eq!(split_bytes("hello world", 11), vec!["hello world"]);
+        assert_eq!(split_bytes("", 10), Vec::<&str>::new());
+    }
+
+    #[test]
+    fn split_bytes_at_whitespace() {
+        assert_eq!(split_bytes("hello world again", 11), vec!["hello world", "again"]);
+        assert_eq!(split_bytes("hello   world", 7), vec!["hello", "world"]);
+    }
+
+    #[test]
+    fn split_bytes_only_whitespace() {
+        assert_eq!(split_bytes("          ", 4), Vec::<&str>::new());
+        assert_eq!(split_bytes(" \t ", 10), Vec::<&str>::new());
+    }
+
+    #[test]
+    fn split_bytes_between_graphemes() {
+        // Each of these is two bytes.
+        assert_eq!(split_bytes("ééééé", 4), vec!["éé", "éé", "é"]);
+        assert_eq!(split_bytes("ééééé", 5), vec!["éé", "éé", "é"]);
+        // A decomposed `é` is a single grapheme of three bytes.
+        assert_eq!(split_bytes("e\u{301}e\u{301}", 5), vec!["e\u{301}", "e\u{301}"]);
+    }
+
+    #[test]
+    fn split_bytes_long_grapheme() {
+        let g = "e\u{301}\u{301}\u{301}";
+        assert_eq!(split_bytes(g, 4), vec!["e\u{301}", "\u{301}\u{301}"]);
+        assert_eq!(split_bytes("\u{1F600}", 2), vec!["\u{1F600}"]);
+        for piece in split_bytes(&g.repeat(3), 5) {
+            assert!(piece.len() <= 5);
+        }
+    }
+}
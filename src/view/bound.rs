@@ -57,6 +57,22 @@ impl Bound {
         }
     }
 
+    /// Splits the bound into two stacked bounds, where the top one has the given height.
+    pub fn split_top(&self, height: u16) -> (Bound, Bound) {
+        let height = cmp::min(height, self.height);
+        let top = Bound::new(self.x, self.y, self.width, height);
+        let bottom = Bound::new(self.x, self.y + height, self.width, self.height - height);
+        (top, bottom)
+    }
+
+    /// Splits the bound into two side-by-side bounds, where the right one has the given width.
+    pub fn split_right(&self, width: u16) -> (Bound, Bound) {
+        let width = cmp::min(width, self.width);
+        let left = self.minus_width(width);
+        let right = Bound::new(self.x + left.width, self.y, width, self.height);
+        (left, right)
+    }
+
     pub fn union(&self, other: &Bound) -> Bound {
         let x1 = cmp::min(self.x, other.x);
         let y1 = cmp::min(self.y, other.y);
@@ -2,6 +2,7 @@ mod bound;
 mod buffer;
 mod style;
 mod terminal;
+mod theme;
 mod ui;
 pub mod widget;
 
@@ -9,5 +10,6 @@ pub use self::bound::Bound;
 pub use self::buffer::Buffer;
 pub use self::style::{Color, Modifier, Style};
 pub use self::terminal::Terminal;
+pub use self::theme::Theme;
 pub use self::ui::UI;
 pub use self::widget::Widget;
@@ -1,5 +1,6 @@
 use std::fmt;
 
+use serde::de::{self, Deserialize, Deserializer, Visitor};
 use termion;
 use termion::color::{Fg, Bg};
 
@@ -45,6 +46,15 @@ macro_rules! make_color {
                     _ => None
                 }
             }
+
+            /// Looks up a color by its name, ignoring case and underscores (e.g. `light_black`).
+            pub fn from_name(name: &str) -> Option<Color> {
+                let name = name.replace('_', "");
+                $(if name.eq_ignore_ascii_case(stringify!($variant)) {
+                    return Some(Color::$variant);
+                })+
+                None
+            }
         }
     }
 }
@@ -86,6 +96,38 @@ make_color! {
     Yellow = 7
 }
 
+/// Colors are named (e.g. `"light_black"`) or given by their IRC color code in config files.
+impl<'de> Deserialize<'de> for Color {
+    fn deserialize<D>(deserializer: D) -> Result<Color, D::Error> where D: Deserializer<'de> {
+        struct ColorVisitor;
+
+        impl<'de> Visitor<'de> for ColorVisitor {
+            type Value = Color;
+
+            fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                write!(f, "a color name or IRC color code")
+            }
+
+            fn visit_str<E>(self, value: &str) -> Result<Color, E> where E: de::Error {
+                Color::from_name(value).ok_or_else(|| {
+                    E::custom(format!("unknown color `{}`", value))
+                })
+            }
+
+            fn visit_i64<E>(self, value: i64) -> Result<Color, E> where E: de::Error {
+                if value >= 0 && value <= 255 {
+                    if let Some(color) = Color::from_u8(value as u8) {
+                        return Ok(color);
+                    }
+                }
+                Err(E::custom(format!("unknown color code `{}`", value)))
+            }
+        }
+
+        deserializer.deserialize_any(ColorVisitor)
+    }
+}
+
 macro_rules! make_modifier {
     ($(#[$attr:meta] $variant:ident),+) => {
         #[derive(Debug, Clone, Copy, Eq, PartialEq)]
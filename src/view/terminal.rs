@@ -2,6 +2,7 @@ use std::io;
 use std::io::{Stdout, Write};
 
 use termion;
+use termion::clear;
 use termion::cursor;
 use termion::raw::{IntoRawMode, RawTerminal};
 use termion::screen::AlternateScreen;
@@ -9,6 +10,10 @@ use termion::screen::AlternateScreen;
 use error;
 use view::{Bound, Buffer, Color, Modifier, Style, Widget};
 
+/// Asks the terminal to mark the start and end of pasted text, see `input::AsyncKeyInput`.
+const ENABLE_BRACKETED_PASTE: &str = "\x1B[?2004h";
+const DISABLE_BRACKETED_PASTE: &str = "\x1B[?2004l";
+
 pub struct Terminal {
     buf_index: usize,
     buffers: [Buffer; 2],
@@ -20,10 +25,13 @@ impl Terminal {
         let (width, height) = termion::terminal_size()?;
         let term_bound = Bound::new(0, 0, width, height);
 
+        let mut stdout = AlternateScreen::from(io::stdout().into_raw_mode()?);
+        write!(stdout, "{}", ENABLE_BRACKETED_PASTE)?;
+
         Ok(Terminal {
             buf_index: 0,
             buffers: [Buffer::empty(term_bound), Buffer::empty(term_bound)],
-            stdout: AlternateScreen::from(io::stdout().into_raw_mode()?),
+            stdout: stdout,
         })
     }
 
@@ -99,6 +107,16 @@ impl Terminal {
         Ok(())
     }
 
+    /// Resizes both internal buffers to the given size, and clears the screen so that the next
+    /// draw repaints everything.
+    pub fn resize(&mut self, width: u16, height: u16) -> error::Result<()> {
+        let term_bound = Bound::new(0, 0, width, height);
+        self.buffers = [Buffer::empty(term_bound), Buffer::empty(term_bound)];
+        write!(self.stdout, "{}", clear::All)?;
+        self.stdout.flush()?;
+        Ok(())
+    }
+
     pub fn current_buf(&self) -> &Buffer {
         &self.buffers[self.buf_index]
     }
@@ -113,3 +131,10 @@ impl Terminal {
         self.buf_index = 1 - self.buf_index;
     }
 }
+
+impl Drop for Terminal {
+    fn drop(&mut self) {
+        let _ = write!(self.stdout, "{}", DISABLE_BRACKETED_PASTE);
+        let _ = self.stdout.flush();
+    }
+}
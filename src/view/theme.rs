@@ -0,0 +1,39 @@
+use view::Color;
+
+/// The colors of the interface, as set in the `[colors]` section of the config.
+#[derive(Clone, Copy, Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Theme {
+    /// The timestamps at the start of each chat line.
+    pub timestamp: Color,
+    /// The topic bar and inactive tabs.
+    pub bar_fg: Color,
+    pub bar_bg: Color,
+    /// The current tab and the scroll marker.
+    pub active_fg: Color,
+    pub active_bg: Color,
+    /// The lines that mention us.
+    pub highlight: Color,
+    /// Inactive tabs with only joins, parts and the like since they were last viewed.
+    pub activity_noise: Color,
+    /// Inactive tabs with unread messages.
+    pub activity_message: Color,
+    /// Inactive tabs with unread mentions or private messages.
+    pub activity_highlight: Color,
+}
+
+impl Default for Theme {
+    fn default() -> Theme {
+        Theme {
+            timestamp: Color::LightBlack,
+            bar_fg: Color::LightWhite,
+            bar_bg: Color::Black,
+            active_fg: Color::Black,
+            active_bg: Color::Magenta,
+            highlight: Color::LightYellow,
+            activity_noise: Color::Cyan,
+            activity_message: Color::Yellow,
+            activity_highlight: Color::LightRed,
+        }
+    }
+}
@@ -3,12 +3,16 @@ use std::io;
 use std::io::Write;
 use std::sync::{Arc, Mutex, MutexGuard};
 
-use irc::proto::ChannelExt;
+use chrono::format::{Item, StrftimeItems};
+use irc::proto::{ChannelExt, ChannelMode, Mode};
+use regex::Regex;
 
+use config::{Config, LogConfig, UiConfig};
 use error;
-use model::Event;
-use view::Terminal;
-use view::widget::{ChatBuf, Input, TabLine};
+use model;
+use model::{BufferId, CaseMapping, Event};
+use view::{Bound, Terminal, Theme};
+use view::widget::{ChatBuf, Input, NickList, TabLine, TopicBar};
 
 #[derive(Clone)]
 pub struct UI {
@@ -16,9 +20,11 @@ pub struct UI {
 }
 
 impl UI {
-    pub fn new() -> error::Result<UI> {
+    pub fn new(config: &UiConfig, log_config: &LogConfig, theme: Theme) -> error::Result<UI> {
+        let state = InterfaceState::new(config, log_config, theme)?;
+        state.set_timestamp_format(&config.timestamp_format)?;
         Ok(UI {
-            state: Arc::new(InterfaceState::new()?),
+            state: Arc::new(state),
         })
     }
 
@@ -26,38 +32,151 @@ impl UI {
         self.state.terminal()
     }
 
-    pub fn has_chat_buf(&self, buf_name: &str) -> error::Result<bool> {
-        self.state.has_chat_buf(buf_name)
+    /// Adds a network along with its status buffer. The first network added becomes current.
+    pub fn new_server(&self, server: &str) -> error::Result<()> {
+        self.state.new_server(server)
     }
 
-    pub fn new_chat_buf(&self, buf_name: &str) -> error::Result<()> {
-        self.state.new_chat_buf(buf_name)
+    pub fn has_chat_buf(&self, id: &BufferId) -> error::Result<bool> {
+        self.state.has_chat_buf(id)
     }
 
-    pub fn remove_chat_buf(&self, buf_name: &str) -> error::Result<()> {
-        self.state.remove_chat_buf(buf_name)
+    pub fn new_chat_buf(&self, id: &BufferId) -> error::Result<()> {
+        self.state.new_chat_buf(id)
     }
 
-    pub fn current_buf(&self) -> error::Result<MutexGuard<String>> {
+    pub fn remove_chat_buf(&self, id: &BufferId) -> error::Result<()> {
+        self.state.remove_chat_buf(id)
+    }
+
+    pub fn current_buf(&self) -> error::Result<MutexGuard<BufferId>> {
         self.state.current_buf()
     }
 
-    pub fn switch_to(&self, buf_name: &str) -> error::Result<()> {
-        self.state.switch_to(buf_name)
+    pub fn switch_to(&self, id: &BufferId) -> error::Result<()> {
+        self.state.switch_to(id)
     }
 
-    pub fn add_event_to_chat_buf(&self, buf_name: &str, event: Event) -> error::Result<()> {
-        self.state.add_event_to_chat_buf(buf_name, event)
+    pub fn add_event_to_chat_buf(&self, id: &BufferId, event: Event) -> error::Result<()> {
+        self.state.add_event_to_chat_buf(id, event)
     }
 
     pub fn add_event_to_current_chat_buf(&self, event: Event) -> error::Result<()> {
         self.state.add_event_to_current_chat_buf(event)
     }
 
+    pub fn scroll_up(&self, lines: usize) -> error::Result<()> {
+        self.state.with_current_chat_buf(|buf, history| buf.scroll_up(history, lines))
+    }
+
+    pub fn scroll_down(&self, lines: usize) -> error::Result<()> {
+        self.state.with_current_chat_buf(|buf, history| buf.scroll_down(history, lines))
+    }
+
+    pub fn page_up(&self) -> error::Result<()> {
+        self.state.with_current_chat_buf(|buf, history| buf.page_up(history))
+    }
+
+    pub fn page_down(&self) -> error::Result<()> {
+        self.state.with_current_chat_buf(|buf, history| buf.page_down(history))
+    }
+
+    pub fn scroll_to_bottom(&self) -> error::Result<()> {
+        self.state.with_current_chat_buf(|buf, history| buf.scroll_to_bottom(history))
+    }
+
+    /// The buffers open on the network.
+    pub fn buffers(&self, server: &str) -> error::Result<Vec<BufferId>> {
+        self.state.buffers(server)
+    }
+
+    /// The nicknames of the members of the channel.
+    pub fn nicknames(&self, chan: &BufferId) -> error::Result<Vec<String>> {
+        self.state.nicknames(chan)
+    }
+
+    /// Our own nick!user@host on the network, if the server has shown it to us.
+    pub fn hostmask(&self, server: &str) -> error::Result<Option<String>> {
+        self.state.hostmask(server)
+    }
+
+    pub fn set_hostmask(&self, server: &str, hostmask: &str) -> error::Result<()> {
+        self.state.set_hostmask(server, hostmask)
+    }
+
+    pub fn add_member(
+        &self, chan: &BufferId, user: &str, casemapping: CaseMapping
+    ) -> error::Result<()> {
+        self.state.add_member(chan, user, casemapping)
+    }
+
+    pub fn remove_member(
+        &self, chan: &BufferId, nickname: &str, casemapping: CaseMapping
+    ) -> error::Result<()> {
+        self.state.remove_member(chan, nickname, casemapping)
+    }
+
+    /// Forgets the members and topic of a channel that we are no longer in.
+    pub fn clear_channel(&self, chan: &BufferId) -> error::Result<()> {
+        self.state.clear_channel(chan)
+    }
+
+    /// Replaces the member list of the channel, e.g. once the server has listed its members.
+    pub fn set_members(&self, chan: &BufferId, members: model::MemberList) -> error::Result<()> {
+        self.state.set_members(chan, members)
+    }
+
+    /// Removes the member from every channel on the network, returning the channels that they
+    /// were in.
+    pub fn remove_member_from_all(
+        &self, server: &str, nickname: &str, casemapping: CaseMapping
+    ) -> error::Result<Vec<BufferId>> {
+        self.state.remove_member_from_all(server, nickname, casemapping)
+    }
+
+    /// Renames the member in every channel on the network, returning the channels that they
+    /// were in.
+    pub fn rename_member(
+        &self, server: &str, old: &str, new: &str, casemapping: CaseMapping
+    ) -> error::Result<Vec<BufferId>> {
+        self.state.rename_member(server, old, new, casemapping)
+    }
+
+    pub fn update_member_mode(
+        &self, chan: &BufferId, mode: &Mode<ChannelMode>, casemapping: CaseMapping
+    ) -> error::Result<()> {
+        self.state.update_member_mode(chan, mode, casemapping)
+    }
+
+    pub fn toggle_nick_list(&self) -> error::Result<()> {
+        self.state.toggle_nick_list()
+    }
+
+    pub fn set_topic(&self, chan: &BufferId, topic: &str) -> error::Result<()> {
+        self.state.set_topic(chan, topic)
+    }
+
+    pub fn set_topic_setter(&self, chan: &BufferId, setter: &str) -> error::Result<()> {
+        self.state.set_topic_setter(chan, setter)
+    }
+
+    pub fn set_timestamp_format(&self, format: &str) -> error::Result<()> {
+        self.state.set_timestamp_format(format)
+    }
+
     pub fn input(&self) -> error::Result<MutexGuard<Input>> {
         self.state.input()
     }
 
+    /// Records a line sent from the input in the buffer, saving it to the history file.
+    pub fn add_to_history(&self, id: &BufferId, line: &str) -> error::Result<()> {
+        self.state.add_to_history(id, line)
+    }
+
+    pub fn resize(&self, width: u16, height: u16) -> error::Result<()> {
+        self.state.resize(width, height)
+    }
+
     pub fn draw_all(&self) -> error::Result<()> {
         self.state.draw_all()
     }
@@ -65,41 +184,112 @@ impl UI {
 
 struct InterfaceState {
     term: Mutex<Terminal>,
-    current_buf: Mutex<String>,
-    chat_bufs: Mutex<HashMap<String, ChatBuf>>,
+    current_buf: Mutex<BufferId>,
+    /// An empty chat buffer laid out for the chat area, which new chat buffers are copied from.
+    blank_chat_buf: Mutex<ChatBuf>,
+    chat_bufs: Mutex<HashMap<BufferId, ChatBuf>>,
+    histories: Mutex<HashMap<BufferId, model::ChatBuf>>,
+    members: Mutex<HashMap<BufferId, model::MemberList>>,
+    topics: Mutex<HashMap<BufferId, model::Topic>>,
+    /// The activity in each buffer since it was last viewed.
+    activity: Mutex<HashMap<BufferId, model::Activity>>,
+    /// Our own nick!user@host on each network.
+    hostmasks: Mutex<HashMap<String, String>>,
     input: Mutex<Input>,
     tabline: Mutex<TabLine>,
+    nick_list: Mutex<NickList>,
+    topic_bar: Mutex<TopicBar>,
+    /// The area below the topic bar that the chat buffers and the nick list share.
+    main_bound: Mutex<Bound>,
+    nick_list_width: u16,
+    /// The number of events kept in each buffer's history.
+    scrollback: usize,
+    /// The format of the input line's prompt, see `UiConfig::prompt`.
+    prompt: String,
+    input_history: Mutex<model::History>,
+    /// Whether the input shows only the history of the current buffer.
+    history_per_buffer: bool,
+    /// Writes the events of each buffer to disk, if logging is enabled.
+    logger: Mutex<Option<model::Logger>>,
+    /// The number of events read back from a buffer's log when it is opened.
+    backlog: usize,
 }
 
 impl InterfaceState {
-    fn new() -> error::Result<InterfaceState> {
+    fn new(
+        config: &UiConfig, log_config: &LogConfig, theme: Theme
+    ) -> error::Result<InterfaceState> {
+        // The history is loaded first, so that any error in it is shown outside of the UI.
+        let data_dir = Config::data_dir();
+        let exclude: Vec<_> = config.history_exclude.iter().filter_map(|pattern| {
+            Regex::new(pattern).ok()
+        }).collect();
+        let input_history = match data_dir {
+            Some(ref dir) => {
+                model::History::load(dir.join("history"), config.history_size, exclude)?
+            }
+            None => model::History::new(config.history_size, exclude),
+        };
+        let logger = data_dir.as_ref().filter(|_| log_config.enabled).map(|dir| {
+            model::Logger::new(
+                dir.join("logs"), log_config.strip_formatting, log_config.exclude.clone()
+            )
+        });
+
         let term = Terminal::new()?;
+        let area = term.current_buf().bound().minus_height(2);
+        let (topic_bound, main_bound) = area.split_top(1);
+        // No buffer is open yet, so the nick list starts out of the way.
+        let (_, nick_bound) = main_bound.split_right(config.nicklist_width);
         let buffer = {
             let mut buf = term.current_buf().clone();
             buf.reset();
-            let new_bound = buf.bound().minus_height(2);
-            buf.resize(new_bound);
+            buf.resize(main_bound);
             buf
         };
 
-        let chat_bufs = {
-            let mut map = HashMap::new();
-            map.insert("*default*".to_owned(), ChatBuf::from_buffer(buffer.clone()));
-            map
+        let blank_chat_buf = {
+            let mut chat_buf = ChatBuf::from_buffer(buffer);
+            chat_buf.set_theme(theme, &model::ChatBuf::new());
+            chat_buf
         };
 
-        let tabline = {
-            let mut tabline = TabLine::from_buffer(&buffer);
-            tabline.add_tab("*default*", true);
-            tabline
-        };
+        let mut tabline = TabLine::new(area.x, area.bottom_border(), area.width);
+        tabline.set_theme(theme);
+        tabline.set_name_width(config.tab_name_width);
+        tabline.set_numbered(config.tab_numbers);
+
+        let mut topic_bar = TopicBar::new(topic_bound.x, topic_bound.y, topic_bound.width);
+        topic_bar.set_theme(theme);
+
+        let mut input = Input::new(area.x, area.bottom_border() + 1, area.width);
+        input.set_history_size(config.history_size);
+        if !config.history_per_buffer {
+            input.set_history(input_history.lines(None));
+        }
 
         Ok(InterfaceState {
             term: Mutex::new(term),
-            current_buf: Mutex::new("*default*".to_owned()),
-            chat_bufs: Mutex::new(chat_bufs),
-            input: Mutex::new(Input::from_buffer(&buffer)),
+            current_buf: Mutex::new(BufferId::default()),
+            blank_chat_buf: Mutex::new(blank_chat_buf),
+            chat_bufs: Mutex::new(HashMap::new()),
+            histories: Mutex::new(HashMap::new()),
+            members: Mutex::new(HashMap::new()),
+            topics: Mutex::new(HashMap::new()),
+            activity: Mutex::new(HashMap::new()),
+            hostmasks: Mutex::new(HashMap::new()),
+            input: Mutex::new(input),
             tabline: Mutex::new(tabline),
+            nick_list: Mutex::new(NickList::new(nick_bound)),
+            topic_bar: Mutex::new(topic_bar),
+            main_bound: Mutex::new(main_bound),
+            nick_list_width: config.nicklist_width,
+            scrollback: config.scrollback,
+            prompt: config.prompt.clone(),
+            input_history: Mutex::new(input_history),
+            history_per_buffer: config.history_per_buffer,
+            logger: Mutex::new(logger),
+            backlog: log_config.backlog,
         })
     }
 
@@ -107,88 +297,457 @@ impl InterfaceState {
         self.term.lock().map_err(|_| error::Error::LockPoisoned { lock: "UI::Terminal" })
     }
 
-    fn has_chat_buf(&self, buf_name: &str) -> error::Result<bool> {
+    fn new_server(&self, server: &str) -> error::Result<()> {
+        let status = BufferId::status(server);
+        let first = self.histories()?.is_empty();
+        self.new_chat_buf(&status)?;
+        if first {
+            self.switch_to(&status)?;
+        }
+        Ok(())
+    }
+
+    fn has_chat_buf(&self, id: &BufferId) -> error::Result<bool> {
         let chat_bufs = self.chat_bufs.lock().map_err(|_| {
             error::Error::LockPoisoned { lock: "UI::ChatBufs" }
         })?;
-        Ok(chat_bufs.contains_key(buf_name))
+        Ok(chat_bufs.contains_key(id))
     }
 
-    fn new_chat_buf(&self, buf_name: &str) -> error::Result<()> {
+    /// Opens a buffer, starting it with the most recent events from its log.
+    fn new_chat_buf(&self, id: &BufferId) -> error::Result<()> {
+        let history = self.backlog(id)?;
         let mut chat_bufs = self.chat_bufs.lock().map_err(|_| {
             error::Error::LockPoisoned { lock: "UI::ChatBufs" }
         })?;
         let mut tabline = self.tabline.lock().map_err(|_| {
             error::Error::LockPoisoned { lock: "UI::TabLine" }
         })?;
-        let mut new_buf = chat_bufs["*default*"].clone();
-        new_buf.reset();
-        chat_bufs.insert(buf_name.to_owned(), new_buf);
-        tabline.add_tab(buf_name, false);
+        let mut histories = self.histories()?;
+        let mut new_buf = self.blank_chat_buf.lock().map_err(|_| {
+            error::Error::LockPoisoned { lock: "UI::BlankChatBuf" }
+        })?.clone();
+        new_buf.redraw(&history);
+        chat_bufs.insert(id.clone(), new_buf);
+        histories.insert(id.clone(), history);
+        tabline.add_tab(id, false);
         Ok(())
     }
 
-    fn remove_chat_buf(&self, buf_name: &str) -> error::Result<()> {
-        let mut current_buf = self.current_buf()?;
+    /// A history for the buffer holding its backlog, followed by a separator marking where the
+    /// backlog ends. A log that cannot be read is shown as an error instead.
+    fn backlog(&self, id: &BufferId) -> error::Result<model::ChatBuf> {
+        let mut history = model::ChatBuf::with_capacity(self.scrollback);
+        let backlog = match self.logger()?.as_ref() {
+            Some(logger) => logger.backlog(id, self.backlog),
+            None => return Ok(history),
+        };
+        match backlog {
+            Ok(ref events) if events.is_empty() => (),
+            Ok(events) => {
+                for event in events {
+                    history.push(event);
+                }
+                history.push(Event::separator(&id.name, "End of backlog"));
+            }
+            Err(e) => history.push(Event::error(&id.name, &e.to_string())),
+        }
+        Ok(history)
+    }
+
+    /// Removes the buffer, switching to its network's status buffer if it was current. Status
+    /// buffers themselves are never removed.
+    fn remove_chat_buf(&self, id: &BufferId) -> error::Result<()> {
+        if id.is_status() {
+            return Ok(());
+        }
+        if *self.current_buf()? == *id {
+            self.switch_to(&BufferId::status(&id.server))?;
+        }
+
         let mut tabline = self.tabline.lock().map_err(|_| {
             error::Error::LockPoisoned { lock: "UI::TabLine" }
         })?;
-        if &*current_buf == buf_name {
-            *current_buf = "*default*".to_owned();
-            tabline.switch_to("*default*")?;
-        }
         let mut chat_bufs = self.chat_bufs.lock().map_err(|_| {
             error::Error::LockPoisoned { lock: "UI::ChatBufs" }
         })?;
-        let _ = chat_bufs.remove(buf_name);
-        tabline.remove_tab(buf_name)?;
+        let _ = chat_bufs.remove(id);
+        let _ = self.histories()?.remove(id);
+        let _ = self.members()?.remove(id);
+        let _ = self.topics()?.remove(id);
+        let _ = self.activity()?.remove(id);
+        if let Some(logger) = self.logger()?.as_mut() {
+            logger.close(id);
+        }
+        tabline.remove_tab(id)?;
         Ok(())
     }
 
-    fn current_buf(&self) -> error::Result<MutexGuard<String>> {
+    fn current_buf(&self) -> error::Result<MutexGuard<BufferId>> {
         self.current_buf.lock().map_err(|_| {
             error::Error::LockPoisoned { lock: "UI::CurrentBuf" }
         })
     }
 
-    fn switch_to(&self, buf_name: &str) -> error::Result<()> {
-        let mut current_buf = self.current_buf()?;
-        *current_buf = buf_name.to_owned();
-        let mut tabline = self.tabline.lock().map_err(|_| {
-            error::Error::LockPoisoned { lock: "UI::TabLine" }
+    /// Switches to the buffer, laying it out again if the chat area has changed since it was last
+    /// shown.
+    fn switch_to(&self, id: &BufferId) -> error::Result<()> {
+        {
+            let mut current_buf = self.current_buf()?;
+            let mut tabline = self.tabline.lock().map_err(|_| {
+                error::Error::LockPoisoned { lock: "UI::TabLine" }
+            })?;
+            tabline.switch_to(id)?;
+            *current_buf = id.clone();
+            let _ = self.activity()?.remove(id);
+            tabline.set_activity(id, model::Activity::default())?;
+        }
+        self.update_input(id)?;
+        self.fit_chat_buf(id)
+    }
+
+    /// The area that the buffer is shown in, which leaves room for the nick list beside channels
+    /// while it is visible.
+    fn chat_bound(&self, id: &BufferId) -> error::Result<Bound> {
+        let main_bound = *self.main_bound()?;
+        Ok(if id.name.is_channel_name() && self.nick_list()?.is_visible() {
+            main_bound.split_right(self.nick_list_width).0
+        } else {
+            main_bound
+        })
+    }
+
+    /// Lays out the buffer again if it does not fit the chat area. Only the buffer being shown is
+    /// kept laid out, and the others are laid out once they are switched to.
+    fn fit_chat_buf(&self, id: &BufferId) -> error::Result<()> {
+        let bound = self.chat_bound(id)?;
+        let mut chat_bufs = self.chat_bufs.lock().map_err(|_| {
+            error::Error::LockPoisoned { lock: "UI::ChatBufs" }
         })?;
-        tabline.switch_to(buf_name)?;
+        let histories = self.histories()?;
+        if let (Some(buf), Some(history)) = (chat_bufs.get_mut(id), histories.get(id)) {
+            if *buf.bound() != bound {
+                buf.resize(bound, history);
+            }
+        }
+        Ok(())
+    }
+
+    /// Shows the current buffer in the prompt of the input line, and its history if each buffer
+    /// has its own. Status buffers are shown by the name of their network.
+    fn update_input(&self, id: &BufferId) -> error::Result<()> {
+        let name = if id.is_status() { &id.server } else { &id.name };
+        let prompt = self.prompt.replace("{buffer}", name).replace("{network}", &id.server);
+        let mut input = self.input()?;
+        input.set_prompt(&prompt);
+        if self.history_per_buffer {
+            input.set_history(self.input_history()?.lines(Some(id)));
+        }
         Ok(())
     }
 
-    fn add_event_to_chat_buf(&self, buf_name: &str, event: Event) -> error::Result<()> {
-        if buf_name.is_channel_name() {
-            self.chat_bufs.lock().map_err(|_| {
-                error::Error::LockPoisoned { lock: "UI::ChatBufs" }
-            })?.get_mut(buf_name).ok_or_else(|| {
-                error::Error::ChannelNotFound { chan: buf_name.to_owned() }
-            }).map(|buf| buf.push_event(&event))
+    fn add_event_to_chat_buf(&self, id: &BufferId, event: Event) -> error::Result<()> {
+        let status = BufferId::status(&id.server);
+        let id = if id.name.is_channel_name() || self.has_chat_buf(id)? {
+            id
         } else {
-            self.chat_bufs.lock().map_err(|_| {
-                error::Error::LockPoisoned { lock: "UI::ChatBufs" }
-            })?.get_mut("*default*").ok_or_else(|| {
-                error::Error::ChannelNotFound { chan: "*default*".to_owned() }
-            }).map(|buf| buf.push_event(&event))
+            &status
+        };
+        let mut histories = self.histories()?;
+        let history = histories.get_mut(id).ok_or_else(|| {
+            error::Error::ChannelNotFound { chan: id.to_string() }
+        })?;
+        let mut chat_bufs = self.chat_bufs.lock().map_err(|_| {
+            error::Error::LockPoisoned { lock: "UI::ChatBufs" }
+        })?;
+        let buf = chat_bufs.get_mut(id).ok_or_else(|| {
+            error::Error::ChannelNotFound { chan: id.to_string() }
+        })?;
+        // The event is only logged and counted once there is a buffer to show it in.
+        let log_error = self.log_event(id, &event)?;
+        self.add_activity(id, event.activity(id.is_conversation() && !id.name.is_channel_name()))?;
+        for event in Some(event).into_iter().chain(log_error) {
+            history.push(event);
+            buf.push_event(history);
         }
+        Ok(())
+    }
+
+    fn logger(&self) -> error::Result<MutexGuard<Option<model::Logger>>> {
+        self.logger.lock().map_err(|_| error::Error::LockPoisoned { lock: "UI::Logger" })
+    }
+
+    /// Writes the event to the buffer's log. If the log cannot be written, the buffer is no
+    /// longer logged, and an error to show in the buffer is returned instead.
+    fn log_event(&self, id: &BufferId, event: &Event) -> error::Result<Option<Event>> {
+        let mut logger = self.logger()?;
+        let logger = match logger.as_mut() {
+            Some(logger) => logger,
+            None => return Ok(None),
+        };
+        Ok(logger.log(id, event).err().map(|e| {
+            logger.disable(id);
+            Event::error(&id.name, &format!("{}, so this buffer is no longer logged", e))
+        }))
     }
 
     fn add_event_to_current_chat_buf(&self, event: Event) -> error::Result<()> {
-        let current_buf = self.current_buf.lock().map_err(|_| {
-            error::Error::LockPoisoned { lock: "UI::CurrentBuf" }
+        let current_buf = self.current_buf()?.clone();
+        self.add_event_to_chat_buf(&current_buf, event)
+    }
+
+    fn activity(&self) -> error::Result<MutexGuard<HashMap<BufferId, model::Activity>>> {
+        self.activity.lock().map_err(|_| error::Error::LockPoisoned { lock: "UI::Activity" })
+    }
+
+    /// Records activity of the specified level in the buffer, unless it is being viewed.
+    fn add_activity(&self, id: &BufferId, level: model::ActivityLevel) -> error::Result<()> {
+        if level == model::ActivityLevel::None || *self.current_buf()? == *id {
+            return Ok(());
+        }
+        let mut activity = self.activity()?;
+        let activity = activity.entry(id.clone()).or_insert_with(model::Activity::default);
+        activity.add(level);
+        self.tabline.lock().map_err(|_| {
+            error::Error::LockPoisoned { lock: "UI::TabLine" }
+        })?.set_activity(id, *activity)
+    }
+
+    fn with_current_chat_buf<F>(&self, f: F) -> error::Result<()>
+    where F: FnOnce(&mut ChatBuf, &model::ChatBuf) {
+        let current_buf = self.current_buf()?;
+        let histories = self.histories()?;
+        let history = histories.get(&*current_buf).ok_or_else(|| {
+            error::Error::ChannelNotFound { chan: current_buf.to_string() }
+        })?;
+        let mut chat_bufs = self.chat_bufs.lock().map_err(|_| {
+            error::Error::LockPoisoned { lock: "UI::ChatBufs" }
         })?;
-        self.add_event_to_chat_buf(&*current_buf, event)
+        chat_bufs.get_mut(&*current_buf).ok_or_else(|| {
+            error::Error::ChannelNotFound { chan: current_buf.to_string() }
+        }).map(|buf| f(buf, history))
+    }
+
+    fn histories(&self) -> error::Result<MutexGuard<HashMap<BufferId, model::ChatBuf>>> {
+        self.histories.lock().map_err(|_| error::Error::LockPoisoned { lock: "UI::Histories" })
+    }
+
+    fn members(&self) -> error::Result<MutexGuard<HashMap<BufferId, model::MemberList>>> {
+        self.members.lock().map_err(|_| error::Error::LockPoisoned { lock: "UI::Members" })
+    }
+
+    fn buffers(&self, server: &str) -> error::Result<Vec<BufferId>> {
+        Ok(self.histories()?.keys().filter(|id| id.server == server).cloned().collect())
+    }
+
+    fn nicknames(&self, chan: &BufferId) -> error::Result<Vec<String>> {
+        Ok(self.members()?.get(chan).map_or_else(Vec::new, |members| {
+            members.iter().map(|member| member.nickname().to_owned()).collect()
+        }))
+    }
+
+    fn hostmasks(&self) -> error::Result<MutexGuard<HashMap<String, String>>> {
+        self.hostmasks.lock().map_err(|_| error::Error::LockPoisoned { lock: "UI::Hostmasks" })
+    }
+
+    fn hostmask(&self, server: &str) -> error::Result<Option<String>> {
+        Ok(self.hostmasks()?.get(server).cloned())
+    }
+
+    fn set_hostmask(&self, server: &str, hostmask: &str) -> error::Result<()> {
+        self.hostmasks()?.insert(server.to_owned(), hostmask.to_owned());
+        Ok(())
+    }
+
+    fn add_member(
+        &self, chan: &BufferId, user: &str, casemapping: CaseMapping
+    ) -> error::Result<()> {
+        self.members()?.entry(chan.clone()).or_insert_with(model::MemberList::new).add(
+            user, casemapping
+        );
+        Ok(())
+    }
+
+    fn remove_member(
+        &self, chan: &BufferId, nickname: &str, casemapping: CaseMapping
+    ) -> error::Result<()> {
+        if let Some(members) = self.members()?.get_mut(chan) {
+            members.remove(nickname, casemapping);
+        }
+        Ok(())
+    }
+
+    fn clear_channel(&self, chan: &BufferId) -> error::Result<()> {
+        let _ = self.members()?.remove(chan);
+        let _ = self.topics()?.remove(chan);
+        Ok(())
+    }
+
+    fn set_members(&self, chan: &BufferId, members: model::MemberList) -> error::Result<()> {
+        self.members()?.insert(chan.clone(), members);
+        Ok(())
+    }
+
+    fn remove_member_from_all(
+        &self, server: &str, nickname: &str, casemapping: CaseMapping
+    ) -> error::Result<Vec<BufferId>> {
+        Ok(self.members()?.iter_mut().filter(|&(chan, _)| chan.server == server).filter_map(
+            |(chan, members)| {
+                if members.remove(nickname, casemapping) { Some(chan.clone()) } else { None }
+            }
+        ).collect())
+    }
+
+    fn rename_member(
+        &self, server: &str, old: &str, new: &str, casemapping: CaseMapping
+    ) -> error::Result<Vec<BufferId>> {
+        Ok(self.members()?.iter_mut().filter(|&(chan, _)| chan.server == server).filter_map(
+            |(chan, members)| {
+                if members.rename(old, new, casemapping) { Some(chan.clone()) } else { None }
+            }
+        ).collect())
+    }
+
+    fn update_member_mode(
+        &self, chan: &BufferId, mode: &Mode<ChannelMode>, casemapping: CaseMapping
+    ) -> error::Result<()> {
+        if let Some(members) = self.members()?.get_mut(chan) {
+            members.update_mode(mode, casemapping);
+        }
+        Ok(())
+    }
+
+    fn nick_list(&self) -> error::Result<MutexGuard<NickList>> {
+        self.nick_list.lock().map_err(|_| error::Error::LockPoisoned { lock: "UI::NickList" })
+    }
+
+    fn topics(&self) -> error::Result<MutexGuard<HashMap<BufferId, model::Topic>>> {
+        self.topics.lock().map_err(|_| error::Error::LockPoisoned { lock: "UI::Topics" })
+    }
+
+    fn set_topic(&self, chan: &BufferId, topic: &str) -> error::Result<()> {
+        self.topics()?.insert(chan.clone(), model::Topic::new(topic));
+        Ok(())
+    }
+
+    fn set_topic_setter(&self, chan: &BufferId, setter: &str) -> error::Result<()> {
+        if let Some(topic) = self.topics()?.get_mut(chan) {
+            topic.set_by = Some(setter.to_owned());
+        }
+        Ok(())
+    }
+
+    fn topic_bar(&self) -> error::Result<MutexGuard<TopicBar>> {
+        self.topic_bar.lock().map_err(|_| error::Error::LockPoisoned { lock: "UI::TopicBar" })
+    }
+
+    fn toggle_nick_list(&self) -> error::Result<()> {
+        self.nick_list()?.toggle();
+        self.layout()
+    }
+
+    /// Changes the timestamp format of every chat buffer, rejecting formats that chrono cannot
+    /// render. An empty format hides the timestamps.
+    fn set_timestamp_format(&self, format: &str) -> error::Result<()> {
+        if StrftimeItems::new(format).any(|item| item == Item::Error) {
+            return Err(error::Error::InvalidTimestampFormat { format: format.to_owned() });
+        }
+
+        let mut chat_bufs = self.chat_bufs.lock().map_err(|_| {
+            error::Error::LockPoisoned { lock: "UI::ChatBufs" }
+        })?;
+        let histories = self.histories()?;
+        for (id, buf) in chat_bufs.iter_mut() {
+            let history = histories.get(id).ok_or_else(|| {
+                error::Error::ChannelNotFound { chan: id.to_string() }
+            })?;
+            buf.set_timestamp_format(format, history);
+        }
+        self.blank_chat_buf.lock().map_err(|_| {
+            error::Error::LockPoisoned { lock: "UI::BlankChatBuf" }
+        })?.set_timestamp_format(format, &model::ChatBuf::new());
+        Ok(())
+    }
+
+    fn input_history(&self) -> error::Result<MutexGuard<model::History>> {
+        self.input_history.lock().map_err(|_| {
+            error::Error::LockPoisoned { lock: "UI::InputHistory" }
+        })
+    }
+
+    fn add_to_history(&self, id: &BufferId, line: &str) -> error::Result<()> {
+        self.input_history()?.push(id, line)
     }
 
     fn input(&self) -> error::Result<MutexGuard<Input>> {
-        self.input.lock().map_err(|_| error::Error::LockPoisoned { lock: "UI::TabLine" })
+        self.input.lock().map_err(|_| error::Error::LockPoisoned { lock: "UI::Input" })
+    }
+
+    fn main_bound(&self) -> error::Result<MutexGuard<Bound>> {
+        self.main_bound.lock().map_err(|_| error::Error::LockPoisoned { lock: "UI::MainBound" })
+    }
+
+    /// Resizes the terminal, and lays out every widget again for its new size.
+    fn resize(&self, width: u16, height: u16) -> error::Result<()> {
+        // The chat area needs at least one line beside the topic bar, tab line and input line.
+        if height < 4 {
+            return Ok(());
+        }
+        self.terminal()?.resize(width, height)?;
+        self.layout()
+    }
+
+    /// Lays out every widget for the size of the terminal, as of its last resize, and the rows
+    /// that the input takes up. Of the chat buffers, only the current one is laid out again, see
+    /// `fit_chat_buf`.
+    fn layout(&self) -> error::Result<()> {
+        let (width, height) = {
+            let term = self.terminal()?;
+            let bound = term.current_buf().bound();
+            (bound.width, bound.height)
+        };
+        if height < 4 {
+            return Ok(());
+        }
+
+        let input_rows = self.input_rows(height)?;
+        let area = Bound::new(0, 0, width, height).minus_height(1 + input_rows);
+        let (topic_bound, main_bound) = area.split_top(1);
+        self.topic_bar()?.resize(topic_bound.x, topic_bound.y, topic_bound.width);
+
+        let (_, nick_bound) = main_bound.split_right(self.nick_list_width);
+        self.nick_list()?.resize(nick_bound);
+        *self.main_bound()? = main_bound;
+
+        {
+            let mut tabline = self.tabline.lock().map_err(|_| {
+                error::Error::LockPoisoned { lock: "UI::TabLine" }
+            })?;
+            let mut input = self.input()?;
+
+            self.blank_chat_buf.lock().map_err(|_| {
+                error::Error::LockPoisoned { lock: "UI::BlankChatBuf" }
+            })?.resize(main_bound, &model::ChatBuf::new());
+            tabline.resize(area.x, area.bottom_border(), area.width);
+            input.resize(area.x, area.bottom_border() + 1, area.width, input_rows);
+        }
+        let current_buf = self.current_buf()?.clone();
+        self.fit_chat_buf(&current_buf)
+    }
+
+    /// The number of rows that the input takes up, which grows with the lines of the message
+    /// being composed so long as the chat area keeps at least one line.
+    fn input_rows(&self, height: u16) -> error::Result<u16> {
+        Ok(self.input()?.rows().min(height.saturating_sub(3)).max(1))
     }
 
     fn draw_all(&self) -> error::Result<()> {
+        // The layout changes whenever the input grows or shrinks by a line.
+        let height = self.terminal()?.current_buf().bound().height;
+        if self.input_rows(height)? != self.input()?.height() {
+            self.layout()?;
+        }
+
         let mut term = self.terminal()?;
         let current_buf = self.current_buf()?;
         let chat_bufs = self.chat_bufs.lock().map_err(|_| {
@@ -197,11 +756,21 @@ impl InterfaceState {
         let tabline = self.tabline.lock().map_err(|_| {
             error::Error::LockPoisoned { lock: "UI::TabLine" }
         })?;
+        let members = self.members()?;
+        let topics = self.topics()?;
+        let mut nick_list = self.nick_list()?;
+        let mut topic_bar = self.topic_bar()?;
         let input = self.input()?;
 
         term.render(chat_bufs.get(&*current_buf).ok_or_else(|| {
-            error::Error::ChannelNotFound { chan: current_buf.clone() }
+            error::Error::ChannelNotFound { chan: current_buf.to_string() }
         })?);
+        if current_buf.name.is_channel_name() {
+            nick_list.redraw(members.get(&*current_buf));
+            term.render(&*nick_list);
+        }
+        topic_bar.redraw(topics.get(&*current_buf));
+        term.render(&*topic_bar);
         term.render(&*tabline);
         term.render(&*input);
         term.draw()?;
@@ -1,50 +1,299 @@
+use std::cmp;
+use std::collections::VecDeque;
 use std::str::Chars;
 
 use unicode_segmentation::UnicodeSegmentation;
 use unicode_width::UnicodeWidthStr;
 
+use config::DEFAULT_TIMESTAMP_FORMAT;
 use model;
-use model::Event;
-use view::{Buffer, Color, Modifier, Style, Widget};
+use model::{Event, EventKind};
+use utils;
+use view::{Bound, Buffer, Color, Modifier, Style, Theme, Widget};
 
+/// The rendered view of a buffer's history. The history itself is owned elsewhere, and must be
+/// passed in whenever the view needs to be laid out again.
+///
+/// The number of lines that each event takes up is kept, so that a view scrolled back only lays
+/// out the events that it shows. These are measured again only when the layout changes, e.g. on
+/// a resize.
 #[derive(Clone)]
 pub struct ChatBuf {
     buf: Buffer,
+    /// The number of lines between the bottom of the view and the end of the history.
+    scroll: usize,
+    /// The maximum number of lines to lay out, used to render views that are scrolled back.
+    limit: Option<usize>,
+    /// The number of lines that have been dropped off of the top of the buffer.
+    overflow: usize,
     starting_x: u16,
     starting_y: u16,
+    /// The extent of each event in the history, in the same order.
+    extents: VecDeque<Extent>,
+    /// The total number of lines that the history takes up.
+    lines: usize,
+    /// The strftime format of the timestamp at the start of each line, or empty for none.
+    timestamp_format: String,
+    theme: Theme,
+}
+
+/// Where an event starts and ends in the layout of the history. Joins and parts continue the
+/// line of those before them, so the layout of an event depends on the column it starts at.
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct Extent {
+    start_x: u16,
+    end_x: u16,
+    /// The number of lines that the event adds to the layout.
+    lines: usize,
 }
 
 impl ChatBuf {
     pub fn from_buffer(buf: Buffer) -> ChatBuf {
         ChatBuf {
             buf: buf,
+            scroll: 0,
+            limit: None,
+            overflow: 0,
             starting_x: 0,
             starting_y: 0,
+            extents: VecDeque::new(),
+            lines: 0,
+            timestamp_format: DEFAULT_TIMESTAMP_FORMAT.to_owned(),
+            theme: Theme::default(),
+        }
+    }
+
+    /// Sets the format of the timestamps, and re-renders the history with them.
+    pub fn set_timestamp_format(&mut self, format: &str, history: &model::ChatBuf) {
+        self.timestamp_format = format.to_owned();
+        self.extents.clear();
+        self.redraw(history);
+    }
+
+    pub fn set_theme(&mut self, theme: Theme, history: &model::ChatBuf) {
+        self.theme = theme;
+        self.extents.clear();
+        self.redraw(history);
+    }
+
+    /// Renders the most recent event in the history. If the view is scrolled back, it stays
+    /// fixed on the same lines rather than following the new event.
+    pub fn push_event(&mut self, history: &model::ChatBuf) {
+        let event = match history.last() {
+            Some(event) => event,
+            None => return,
+        };
+        // The extents are only kept up to date one event at a time.
+        if self.extents.len() + 1 < history.len() {
+            self.extents.clear();
+            return self.redraw(history);
+        }
+
+        let evicted = self.evict(history);
+        let start_x = self.extents.back().map_or(0, |extent| extent.end_x);
+        let extent = self.scratch().measure(event, start_x);
+        self.extents.push_back(extent);
+        self.lines += extent.lines;
+
+        if self.scroll > 0 {
+            self.scroll += extent.lines;
+            if evicted {
+                self.redraw(history);
+            } else {
+                self.draw_scroll_marker();
+            }
+        } else if evicted && self.lines <= self.buf.height() as usize {
+            // The lines of the evicted events are still in view.
+            self.redraw(history);
+        } else {
+            self.render_event(event);
+        }
+    }
+
+    /// Scrolls the view back through the history by the specified number of lines.
+    pub fn scroll_up(&mut self, history: &model::ChatBuf, lines: usize) {
+        self.scroll += lines;
+        self.redraw(history);
+    }
+
+    /// Scrolls the view forward through the history by the specified number of lines.
+    pub fn scroll_down(&mut self, history: &model::ChatBuf, lines: usize) {
+        if self.scroll > 0 {
+            self.scroll = self.scroll.saturating_sub(lines);
+            self.redraw(history);
+        }
+    }
+
+    pub fn page_up(&mut self, history: &model::ChatBuf) {
+        let page = self.page_size();
+        self.scroll_up(history, page);
+    }
+
+    pub fn page_down(&mut self, history: &model::ChatBuf) {
+        let page = self.page_size();
+        self.scroll_down(history, page);
+    }
+
+    /// Pins the view back to the most recent events.
+    pub fn scroll_to_bottom(&mut self, history: &model::ChatBuf) {
+        if self.scroll > 0 {
+            self.scroll = 0;
+            self.redraw(history);
         }
     }
 
-    pub fn redraw_from_model(&mut self, model: model::ChatBuf) {
+    pub fn is_scrolled(&self) -> bool {
+        self.scroll > 0
+    }
+
+    pub fn bound(&self) -> &Bound {
+        self.buf.bound()
+    }
+
+    /// Resizes the view to the given bound, and re-wraps the history to fit it. The history is
+    /// only measured again if the width changes.
+    pub fn resize(&mut self, bound: Bound, history: &model::ChatBuf) {
+        if bound.width != self.buf.width() {
+            self.extents.clear();
+        }
+        self.buf = Buffer::empty(bound);
+        self.redraw(history);
+    }
+
+    /// Re-renders the visible portion of the buffer from the history, clamping the scroll
+    /// offset to the length of the history. Only the events in view are laid out, unless the
+    /// history has to be measured again.
+    pub fn redraw(&mut self, history: &model::ChatBuf) {
+        if self.extents.len() != history.len() {
+            self.measure_all(history);
+        }
+        let height = self.buf.height() as usize;
+        self.scroll = cmp::min(self.scroll, self.lines.saturating_sub(height));
+
+        // The layout starts from the last event that starts a line at or above the view.
+        let bottom = self.lines - self.scroll;
+        let top = bottom.saturating_sub(height);
+        let (mut first, mut first_line) = (0, 0);
+        let mut line = self.lines;
+        for (i, extent) in self.extents.iter().enumerate().rev() {
+            line -= extent.lines;
+            if extent.start_x == 0 && line <= top {
+                first = i;
+                first_line = line;
+                break;
+            }
+        }
+
+        let events = history.iter().skip(first);
+        if self.scroll == 0 {
+            let layout = self.layout(events, None);
+            self.buf = layout.buf;
+            self.overflow = layout.overflow;
+            self.starting_x = layout.starting_x;
+            self.starting_y = layout.starting_y;
+        } else {
+            self.buf = self.layout(events, Some(bottom - first_line)).buf;
+            self.draw_scroll_marker();
+        }
+    }
+
+    /// Drops the extents of the events that the history has dropped to make room for the most
+    /// recent one, returning whether there were any.
+    fn evict(&mut self, history: &model::ChatBuf) -> bool {
+        let evicted = (self.extents.len() + 1).saturating_sub(history.len());
+        for _ in 0..evicted {
+            if let Some(extent) = self.extents.pop_front() {
+                self.lines -= extent.lines;
+            }
+        }
+        if evicted == 0 {
+            return false;
+        }
+
+        // Joins and parts that continued the line of an evicted event now start a line.
+        let mut scratch = self.scratch();
+        let mut start_x = 0;
+        for (i, event) in history.iter().enumerate().take(self.extents.len()) {
+            if self.extents[i].start_x == start_x {
+                break;
+            }
+            let extent = scratch.measure(event, start_x);
+            self.lines = self.lines - self.extents[i].lines + extent.lines;
+            self.extents[i] = extent;
+            start_x = extent.end_x;
+        }
+        true
+    }
+
+    /// Measures the extent of every event in the history.
+    fn measure_all(&mut self, history: &model::ChatBuf) {
+        let mut scratch = self.scratch();
+        self.extents.clear();
+        self.lines = 0;
+        for event in history {
+            let start_x = self.extents.back().map_or(0, |extent| extent.end_x);
+            let extent = scratch.measure(event, start_x);
+            self.extents.push_back(extent);
+            self.lines += extent.lines;
+        }
+    }
+
+    /// Measures the extent of the event when it starts at the specified column, by laying it out
+    /// in this view, which should be a scratch view.
+    fn measure(&mut self, event: &Event, start_x: u16) -> Extent {
         self.reset();
-        for event in &model {
-            self.push_event(event)
+        self.starting_x = start_x;
+        let before = self.line_count();
+        self.render_event(event);
+        Extent {
+            start_x: start_x,
+            end_x: self.starting_x,
+            lines: self.line_count() - before,
         }
     }
 
-    pub fn push_event(&mut self, event: &Event) {
-        match event {
-            &Event::PrivMessage(_, _, _) | &Event::Notice(_, _, _) => {
+    /// A view one line high and as wide as this one, in which events are measured.
+    fn scratch(&self) -> ChatBuf {
+        let bound = Bound::new(self.buf.bound().x, self.buf.bound().y, self.buf.width(), 1);
+        let mut scratch = ChatBuf::from_buffer(Buffer::empty(bound));
+        scratch.timestamp_format = self.timestamp_format.clone();
+        scratch.theme = self.theme;
+        scratch
+    }
+
+    fn render_event(&mut self, event: &Event) {
+        if let &EventKind::Separator(_, ref label) = &event.kind {
+            return self.render_separator(label);
+        }
+
+        let starts_line = match &event.kind {
+            &EventKind::JoinPart(_, _, _) => self.starting_x == 0,
+            _ => {
                 if self.starting_x != 0 {
                     self.starting_x = 0;
                     self.starting_y += 1;
                 }
+                true
             }
-            _ => (),
+        };
+
+        let mut indent = event.indent();
+        if starts_line && !self.timestamp_format.is_empty() {
+            let timestamp = format!("{} ", event.time.format(&self.timestamp_format));
+            indent += timestamp.width();
+            self.push_line(&timestamp, Some(Style::from(self.theme.timestamp)), 0);
         }
 
-        self.push_line(&event.to_string(), event.style());
+        let style = if event.highlight {
+            let style = event.style().unwrap_or_default().fg(self.theme.highlight);
+            Some(style.modifier(Modifier::Bold))
+        } else {
+            event.style()
+        };
+        self.push_line(&event.to_string(), style, indent);
 
-        match event {
-            &Event::JoinPart(_, _, _) => {
+        match &event.kind {
+            &EventKind::JoinPart(_, _, _) => {
                 self.starting_x += 1;
             }
             _ => {
@@ -54,12 +303,65 @@ impl ChatBuf {
         }
     }
 
-    fn push_line(&mut self, line: &str, style: Option<Style>) {
+    /// Renders a rule across the whole width of the view, labelled at its start and without a
+    /// timestamp.
+    fn render_separator(&mut self, label: &str) {
+        if self.starting_x != 0 {
+            self.starting_x = 0;
+            self.starting_y += 1;
+        }
+
+        let width = self.buf.width() as usize;
+        let label = utils::truncate(&format!("── {} ", label), width);
+        let rule = format!("{}{}", label, "─".repeat(width.saturating_sub(label.width())));
+        self.push_line(&rule, Some(Style::from(self.theme.timestamp)), 0);
+
+        self.starting_x = 0;
+        self.starting_y += 1;
+    }
+
+    /// Lays out the events into a fresh buffer of the same size, stopping after the specified
+    /// number of lines if a limit is given.
+    fn layout<'a, I>(&self, events: I, limit: Option<usize>) -> ChatBuf
+    where I: IntoIterator<Item = &'a Event> {
+        let mut layout = ChatBuf::from_buffer(Buffer::empty(*self.buf.bound()));
+        layout.limit = limit;
+        layout.timestamp_format = self.timestamp_format.clone();
+        layout.theme = self.theme;
+        for event in events {
+            layout.render_event(event);
+        }
+        layout
+    }
+
+    /// The total number of lines that have been laid out, including those dropped off the top.
+    fn line_count(&self) -> usize {
+        self.overflow + self.starting_y as usize + if self.starting_x != 0 { 1 } else { 0 }
+    }
+
+    fn page_size(&self) -> usize {
+        cmp::max(self.buf.height() as usize, 2) - 1
+    }
+
+    fn draw_scroll_marker(&mut self) {
+        let (x, y) = (self.buf.bound().x, self.buf.bound().bottom_border() - 1);
+        let width = self.buf.width() as usize;
+        let style = Style::default().fg(self.theme.active_fg).bg(self.theme.active_bg);
+        let marker = format!(" -- {} more lines below -- ", self.scroll);
+        let marker: String = marker.chars().chain(" ".repeat(width).chars()).take(width).collect();
+        self.buf.set_str_styled(x, y, &marker, style);
+    }
+
+    /// Renders the line at the cursor, indenting any lines that it wraps onto by the given width.
+    fn push_line(&mut self, line: &str, style: Option<Style>, indent: usize) {
+        // Indenting more than half of the view would leave too little room for the text.
+        let indent = if indent < self.buf.width() as usize / 2 { indent as u16 } else { 0 };
         let graphemes = UnicodeSegmentation::graphemes(line, true);
         let mut chars = line.chars();
 
         let mut skip = 0;
-        let mut style = style.unwrap_or_default();
+        let base = style.unwrap_or_default();
+        let mut style = base;
         let mut x = self.starting_x;
         let mut y = self.starting_y;
 
@@ -91,8 +393,9 @@ impl ChatBuf {
                             chars = local;
                         }
                     } else {
-                        style = style.fg(Color::Reset);
-                        style = style.bg(Color::Reset);
+                        // A bare color code resets to the line's base colors.
+                        style = style.fg(base.fg);
+                        style = style.bg(base.bg);
                     }
 
                     continue;
@@ -112,26 +415,35 @@ impl ChatBuf {
 
             // On a newline, carriage return and move to the next line.
             if g == "\n" {
-                x = 0;
+                x = indent;
                 y += 1;
                 continue;
             }
 
             // Handle wrapping
             if x >= self.buf.width() {
-                x = 0;
+                x = indent;
                 y += 1;
             }
 
+            // Stop once the line limit has been reached.
+            if let Some(limit) = self.limit {
+                if self.overflow + y as usize >= limit {
+                    break;
+                }
+            }
+
             // Handle overflow
             if y >= self.buf.height() {
                 self.buf.drop_top_line();
+                self.overflow += 1;
                 y -= 1;
             }
 
             // Set the cell to this grapheme, set the style, and moves the pointer.
-            self.buf.set(x, y, g);
-            self.buf.set_style(x, y, style);
+            let (cell_x, cell_y) = (self.buf.bound().x + x, self.buf.bound().y + y);
+            self.buf.set(cell_x, cell_y, g);
+            self.buf.set_style(cell_x, cell_y, style);
             x += g.width() as u16;
         }
 
@@ -141,6 +453,8 @@ impl ChatBuf {
 
     pub fn reset(&mut self) {
         self.buf.reset();
+        self.scroll = 0;
+        self.overflow = 0;
         self.starting_x = 0;
         self.starting_y = 0;
     }
@@ -174,3 +488,167 @@ impl<'a> CharsExt for Chars<'a> {
         )
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use model;
+    use model::Event;
+    use view::{Bound, Buffer};
+    use super::ChatBuf;
+
+    /// A view of the given size onto the history, without timestamps.
+    fn chat_view(width: u16, height: u16, history: &model::ChatBuf) -> ChatBuf {
+        let mut view = ChatBuf::from_buffer(Buffer::empty(Bound::new(0, 0, width, height)));
+        view.set_timestamp_format("", history);
+        view
+    }
+
+    fn rows(view: &ChatBuf) -> Vec<String> {
+        let bound = *view.buf.bound();
+        (bound.y..bound.bottom_border()).map(|y| {
+            (bound.x..bound.right_border()).map(|x| view.buf.get(x, y)).collect::<String>()
+        }).map(|row| row.trim_end().to_owned()).collect()
+    }
+
+    /// Checks that the view shows the same lines as the whole history laid out at once, scrolled
+    /// back by the view's offset, and that its cached extents match a fresh measurement.
+    fn check(view: &ChatBuf, history: &model::ChatBuf) {
+        let (width, height) = (view.buf.width(), view.buf.height() as usize);
+        let mut tall = chat_view(width, 1000, history);
+        tall.redraw(history);
+        let lines = rows(&tall);
+        let bottom = tall.lines - view.scroll;
+        let mut expected = lines[bottom.saturating_sub(height)..bottom].to_vec();
+        expected.resize(height, String::new());
+
+        let mut actual = rows(view);
+        if view.scroll > 0 {
+            let marker = actual.pop().unwrap();
+            assert!(marker.starts_with(&format!(" -- {} more", view.scroll)), "{:?}", marker);
+            expected.pop();
+        }
+        assert_eq!(actual, expected);
+        assert_eq!(view.extents, tall.extents);
+        assert_eq!(view.lines, tall.lines);
+    }
+
+    fn messages(capacity: usize, count: usize) -> model::ChatBuf {
+        let mut history = model::ChatBuf::with_capacity(capacity);
+        for i in 0..count {
+            history.push(Event::message(Some("n"), "#c", &format!("m{}", i)));
+        }
+        history
+    }
+
+    #[test]
+    fn scroll_within_the_history() {
+        let history = messages(100, 10);
+        let mut view = chat_view(20, 4, &history);
+        assert_eq!(rows(&view), vec!["[n] m6", "[n] m7", "[n] m8", "[n] m9"]);
+
+        view.scroll_up(&history, 1);
+        assert_eq!(view.scroll, 1);
+        assert_eq!(rows(&view)[..3], ["[n] m5", "[n] m6", "[n] m7"]);
+        check(&view, &history);
+
+        // The view stops once the first line is at the top.
+        view.scroll_up(&history, 100);
+        assert_eq!(view.scroll, 6);
+        assert_eq!(rows(&view)[0], "[n] m0");
+        check(&view, &history);
+
+        view.page_down(&history);
+        assert_eq!(view.scroll, 3);
+        check(&view, &history);
+        view.scroll_down(&history, 3);
+        assert!(!view.is_scrolled());
+        check(&view, &history);
+    }
+
+    #[test]
+    fn history_shorter_than_the_view() {
+        let history = messages(100, 2);
+        let mut view = chat_view(20, 4, &history);
+        view.scroll_up(&history, 1);
+        assert!(!view.is_scrolled());
+        assert_eq!(rows(&view), vec!["[n] m0", "[n] m1", "", ""]);
+    }
+
+    #[test]
+    fn stay_in_place_while_scrolled() {
+        let mut history = messages(100, 10);
+        let mut view = chat_view(20, 4, &history);
+        view.scroll_up(&history, 2);
+        let shown = rows(&view)[..3].to_vec();
+
+        history.push(Event::message(Some("n"), "#c", "a message of two lines"));
+        view.push_event(&history);
+        assert_eq!(view.scroll, 4);
+        assert_eq!(rows(&view)[..3], shown[..]);
+        check(&view, &history);
+
+        view.scroll_to_bottom(&history);
+        check(&view, &history);
+    }
+
+    #[test]
+    fn evict_while_scrolled() {
+        let mut history = messages(6, 6);
+        let mut view = chat_view(20, 4, &history);
+        view.scroll_up(&history, 1);
+        for i in 6..9 {
+            history.push(Event::message(Some("n"), "#c", &format!("m{}", i)));
+            view.push_event(&history);
+            check(&view, &history);
+        }
+        // The lines that were in view were evicted, so the view stops at the oldest line left.
+        assert_eq!(view.scroll, 2);
+        assert_eq!(rows(&view)[0], "[n] m3");
+    }
+
+    #[test]
+    fn evict_a_line_of_joins() {
+        let mut history = model::ChatBuf::with_capacity(5);
+        let mut view = chat_view(12, 3, &history);
+        history.push(Event::message(Some("n"), "#c", "hi"));
+        view.push_event(&history);
+        for nick in &["aaa", "bbb", "ccc", "ddd", "eee", "fff"] {
+            history.push(Event::joined(Some(nick), "#c"));
+            view.push_event(&history);
+            check(&view, &history);
+        }
+        view.scroll_up(&history, 1);
+        check(&view, &history);
+    }
+
+    #[test]
+    fn measure_again_on_resize() {
+        let mut history = messages(100, 5);
+        history.push(Event::message(Some("n"), "#c", "a message that wraps onto more lines"));
+        let mut view = chat_view(40, 4, &history);
+        view.scroll_up(&history, 1);
+        let lines = view.lines;
+
+        view.resize(Bound::new(0, 0, 12, 4), &history);
+        assert!(view.lines > lines);
+        check(&view, &history);
+        view.resize(Bound::new(0, 0, 40, 4), &history);
+        assert_eq!(view.lines, lines);
+        check(&view, &history);
+    }
+
+    #[test]
+    fn keep_the_measurements_on_a_change_of_height() {
+        let history = messages(100, 10);
+        let mut view = chat_view(20, 4, &history);
+        view.scroll_up(&history, 3);
+        view.resize(Bound::new(0, 0, 20, 6), &history);
+        assert_eq!(view.extents.len(), history.len());
+        assert_eq!(view.scroll, 3);
+        check(&view, &history);
+        view.resize(Bound::new(0, 0, 20, 9), &history);
+        // The view cannot scroll back past the first line.
+        assert_eq!(view.scroll, 1);
+        check(&view, &history);
+    }
+}
@@ -1,16 +1,71 @@
+use std::borrow::Cow;
 use std::io;
 use std::io::Write;
 
 use termion::cursor;
+use unicode_segmentation::UnicodeSegmentation;
+use unicode_width::UnicodeWidthStr;
 
 use error;
+use model;
 use view::{Bound, Buffer, Widget};
 
+/// The number of killed pieces of text that are kept for yanking.
+const KILL_RING_SIZE: usize = 16;
+
+/// The most rows that the input grows to when composing a message of several lines.
+const MAX_ROWS: u16 = 5;
+
 pub struct Input {
     buf: Buffer,
+    /// The history of sent lines, oldest first, followed by the line being written.
     string: Vec<String>,
+    /// The number of sent lines kept in the history.
+    history_size: usize,
     curr: usize,
-    cursor: u16,
+    /// The number of grapheme clusters before the cursor.
+    cursor: usize,
+    /// Text shown before the line being edited, such as the name of the current buffer.
+    prompt: String,
+    /// The display column of the line that is shown at the left edge of the editable area.
+    scroll: usize,
+    /// The first line of a message of several lines that is shown.
+    top_row: usize,
+    /// Whether sending the message has to be confirmed by sending it again.
+    awaiting_confirmation: bool,
+    completion: Option<Completion>,
+    /// Text removed by the kill commands, most recent last.
+    kill_ring: Vec<String>,
+    yank: Option<Yank>,
+    search: Option<Search>,
+}
+
+/// A tab completion in progress. The chosen candidate replaces the completed word, and is kept
+/// once the user presses any key other than those that cycle through the candidates.
+struct Completion {
+    /// The index at which the completed word starts.
+    start: usize,
+    candidates: Vec<String>,
+    current: usize,
+}
+
+/// A reverse incremental search through the history, which shows the latest line containing the
+/// query in place of the line being edited.
+struct Search {
+    query: String,
+    /// Whether no line contains the query.
+    failed: bool,
+    /// The line and cursor before the search, which are restored if it is cancelled.
+    original: (usize, usize),
+}
+
+/// The text most recently yanked from the kill ring, which can be replaced by earlier kills until
+/// the next edit.
+struct Yank {
+    /// The index at which the yanked text starts.
+    start: usize,
+    /// The index of the yanked text in the kill ring.
+    index: usize,
 }
 
 impl Input {
@@ -18,8 +73,17 @@ impl Input {
         Input {
             buf: Buffer::empty(Bound::new(x, y, width, 1)),
             string: vec![String::new()],
+            history_size: model::DEFAULT_HISTORY_SIZE,
             curr: 0,
             cursor: 0,
+            prompt: String::new(),
+            scroll: 0,
+            top_row: 0,
+            awaiting_confirmation: false,
+            completion: None,
+            kill_ring: Vec::new(),
+            yank: None,
+            search: None,
         }
     }
 
@@ -27,30 +91,205 @@ impl Input {
         Input::new(buf.bound().x, buf.bound().y + buf.bound().height + 1, buf.bound().width)
     }
 
+    /// Moves the input to the given position and size, keeping its contents.
+    pub fn resize(&mut self, x: u16, y: u16, width: u16, height: u16) {
+        self.buf.resize(Bound::new(x, y, width, height));
+        self.redraw();
+    }
+
+    pub fn height(&self) -> u16 {
+        self.buf.height()
+    }
+
+    /// The number of rows needed to show every line of the message, up to `MAX_ROWS`.
+    pub fn rows(&self) -> u16 {
+        (self.line_count() as u16).min(MAX_ROWS)
+    }
+
+    /// The number of lines in the message being edited.
+    pub fn line_count(&self) -> usize {
+        self.string[self.curr].split('\n').count()
+    }
+
+    pub fn is_awaiting_confirmation(&self) -> bool {
+        self.awaiting_confirmation
+    }
+
+    /// Marks the message as needing to be sent again before it is sent. Any edit to the message
+    /// cancels this.
+    pub fn await_confirmation(&mut self) {
+        self.awaiting_confirmation = true;
+    }
+
+    /// Sets the text shown before the line being edited.
+    pub fn set_prompt(&mut self, prompt: &str) {
+        self.prompt = prompt.to_owned();
+        self.redraw();
+    }
+
     pub fn get_content(&self) -> &str {
         &self.string[self.curr]
     }
 
+    /// Clears the input after its line is sent, keeping the line in the history. A line sent
+    /// from the history is moved to the end of it.
     pub fn reset(&mut self) {
-        self.string.push(String::new());
-        self.buf.reset();
+        self.before_edit();
+        if !self.string[self.curr].is_empty() {
+            self.string.push(String::new());
+        }
+        self.trim_history();
+        self.curr = self.latest();
         self.cursor = 0;
+        self.redraw();
+    }
+
+    /// Sets the number of sent lines kept in the history, dropping the oldest lines past it.
+    pub fn set_history_size(&mut self, size: usize) {
+        self.history_size = size;
+        self.set_history(self.string[..self.latest()].to_vec());
+    }
+
+    /// Replaces the history of sent lines, oldest first, keeping the line being written.
+    pub fn set_history(&mut self, history: Vec<String>) {
+        let line = self.string.pop().unwrap_or_default();
+        self.string = history;
+        self.string.push(line);
+        self.trim_history();
         self.curr = self.latest();
+        self.search = None;
+        self.bound_cursor();
+        self.redraw();
     }
 
-    pub fn add_char(&mut self, c: char) {
+    pub fn is_searching(&self) -> bool {
+        self.search.is_some()
+    }
+
+    /// Starts a reverse incremental search through the history.
+    pub fn start_search(&mut self) {
+        self.end_replacements();
+        self.search = Some(Search {
+            query: String::new(),
+            failed: false,
+            original: (self.curr, self.cursor),
+        });
+        self.redraw();
+    }
+
+    /// Adds a character to the query, looking for it from the line currently found.
+    pub fn search_push(&mut self, c: char) {
+        let curr = self.curr;
+        if let Some(ref mut search) = self.search {
+            search.query.push(c);
+        }
+        self.search_from(curr);
+    }
+
+    /// Removes the last character of the query, looking for it from where the search started.
+    pub fn search_pop(&mut self) {
+        let original = match self.search {
+            Some(ref mut search) => {
+                search.query.pop();
+                search.original.0
+            }
+            None => return,
+        };
+        self.search_from(original);
+    }
+
+    /// Looks for an older line containing the query.
+    pub fn search_next(&mut self) {
+        if self.curr > 0 {
+            let curr = self.curr - 1;
+            self.search_from(curr);
+        } else if let Some(ref mut search) = self.search {
+            search.failed = true;
+            self.redraw();
+        }
+    }
+
+    /// Ends the search, keeping the line that was found for editing.
+    pub fn accept_search(&mut self) {
+        self.search = None;
+        self.redraw();
+    }
+
+    /// Ends the search, going back to the line that was being edited before it.
+    pub fn cancel_search(&mut self) {
+        if let Some(search) = self.search.take() {
+            let (curr, cursor) = search.original;
+            self.curr = curr;
+            self.cursor = cursor;
+        }
+        self.redraw();
+    }
+
+    /// The word that ends at the cursor, along with the byte index at which it starts.
+    pub fn word_before_cursor(&self) -> (usize, &str) {
+        let line = &self.string[self.curr][..self.offset()];
+        let start = line.rfind([' ', '\n']).map_or(0, |i| i + 1);
+        (start, &line[start..])
+    }
+
+    pub fn is_completing(&self) -> bool {
+        self.completion.is_some()
+    }
+
+    /// Replaces the word before the cursor with the first of the candidates, or with the last if
+    /// completing backwards. Does nothing if there are no candidates.
+    pub fn complete(&mut self, candidates: Vec<String>, backwards: bool) {
+        if candidates.is_empty() {
+            return;
+        }
         self.before_edit();
 
-        let cursor = self.cursor as usize;
-        let (x, y) = (self.cursor, self.buf.bound().y);
-        if cursor == self.string[self.curr].len() {
-            self.string[self.curr].push(c);
-            self.buf.set(x, y, &c.to_string());
-        } else {
-            self.string[self.curr].insert(cursor, c);
-            self.buf.set_str(x, y, &self.string[self.curr][cursor..]);
+        let start = self.word_before_cursor().0;
+        let current = if backwards { candidates.len() - 1 } else { 0 };
+        self.completion = Some(Completion {
+            start: start,
+            candidates: candidates,
+            current: current,
+        });
+        self.insert_completion();
+    }
+
+    /// Replaces the current completion with the next candidate, or the previous if backwards.
+    pub fn cycle_completion(&mut self, backwards: bool) {
+        if let Some(ref mut completion) = self.completion {
+            let len = completion.candidates.len();
+            completion.current = if backwards {
+                (completion.current + len - 1) % len
+            } else {
+                (completion.current + 1) % len
+            };
         }
-        self.cursor += 1;
+        self.insert_completion();
+    }
+
+    pub fn add_char(&mut self, c: char) {
+        self.before_edit();
+
+        // A combining character joins the grapheme before it, so the cursor is found again from
+        // the end of the inserted character rather than simply advanced.
+        let offset = self.offset();
+        self.string[self.curr].insert(offset, c);
+        self.set_offset(offset + c.len_utf8());
+        self.redraw();
+    }
+
+    /// Inserts text at the cursor, such as a paste. Tabs become spaces, and any other control
+    /// characters besides newlines are dropped.
+    pub fn add_str(&mut self, text: &str) {
+        self.before_edit();
+
+        let text: String = text.chars().map(|c| if c == '\t' { ' ' } else { c }).filter(|&c| {
+            c == '\n' || !c.is_control()
+        }).collect();
+        let offset = self.offset();
+        self.string[self.curr].insert_str(offset, &text);
+        self.set_offset(offset + text.len());
+        self.redraw();
     }
 
     pub fn backspace(&mut self) {
@@ -61,59 +300,201 @@ impl Input {
             return;
         }
 
-        // Move the cursor back one spot.
+        // Remove the whole grapheme before the cursor from the internal string buffer.
+        let end = self.offset();
         self.cursor -= 1;
-
-        // Remove the character from the internal string buffer.
-        self.string[self.curr].remove(self.cursor as usize);
-
-        let cursor = self.cursor as usize;
-        if cursor == self.string[self.curr].len() {
-            // Set the character to a blank on the visual buffer.
-            let (x, y) = (self.cursor, self.buf.bound().y);
-            self.buf.set(x, y, &" ".to_owned());
-        } else {
-            // Redraw the the whole visual buffer.
-            self.redraw();
-        }
+        let start = self.offset();
+        self.string[self.curr].replace_range(start..end, "");
+        self.redraw();
     }
 
     pub fn move_left(&mut self) {
+        self.end_replacements();
         if self.cursor > 0 {
             self.cursor -= 1;
         }
+        self.redraw();
     }
 
     pub fn move_right(&mut self) {
-        if (self.cursor as usize) < self.string[self.curr].len() {
+        self.end_replacements();
+        if self.cursor < self.len() {
             self.cursor += 1;
         }
+        self.redraw();
     }
 
+    /// Moves the cursor to the line above in a message of several lines, or otherwise to the
+    /// previous message in the history.
     pub fn move_up(&mut self) {
-        if self.curr > 0 {
+        self.end_replacements();
+        let (row, column) = self.cursor_position();
+        if row > 0 {
+            self.move_to(row - 1, column);
+        } else if self.curr > 0 {
             self.curr -= 1;
-            self.redraw();
             self.bound_cursor();
+            self.redraw();
         }
     }
 
+    /// Moves the cursor to the line below in a message of several lines, or otherwise to the
+    /// next message in the history.
     pub fn move_down(&mut self) {
-        if self.curr < self.latest() {
+        self.end_replacements();
+        let (row, column) = self.cursor_position();
+        if row + 1 < self.line_count() {
+            self.move_to(row + 1, column);
+        } else if self.curr < self.latest() {
             self.curr += 1;
-            self.redraw();
             self.bound_cursor();
+            self.redraw();
         }
     }
 
+    /// Moves the cursor to the start of its line.
+    pub fn move_home(&mut self) {
+        self.end_replacements();
+        let start = self.line_start();
+        self.set_offset(start);
+        self.redraw();
+    }
+
+    /// Moves the cursor to the end of its line.
+    pub fn move_end(&mut self) {
+        self.end_replacements();
+        let end = self.line_end();
+        self.set_offset(end);
+        self.redraw();
+    }
+
+    /// Moves the cursor to the start of the word before it.
+    pub fn move_word_left(&mut self) {
+        self.end_replacements();
+        let start = word_start(&self.string[self.curr], self.offset(), is_word);
+        self.set_offset(start);
+        self.redraw();
+    }
+
+    /// Moves the cursor to the end of the word after it.
+    pub fn move_word_right(&mut self) {
+        self.end_replacements();
+        let end = word_end(&self.string[self.curr], self.offset(), is_word);
+        self.set_offset(end);
+        self.redraw();
+    }
+
+    /// Deletes the grapheme under the cursor.
+    pub fn delete(&mut self) {
+        self.before_edit();
+
+        let offset = self.offset();
+        let len = self.string[self.curr][offset..].graphemes(true).next().map_or(0, str::len);
+        if len > 0 {
+            self.string[self.curr].replace_range(offset..offset + len, "");
+            self.redraw();
+        }
+    }
+
+    /// Kills everything back to the previous whitespace, as Ctrl-W does in a shell.
+    pub fn kill_word_before(&mut self) {
+        let offset = self.offset();
+        let start = word_start(&self.string[self.curr], offset, |g| {
+            !g.chars().all(char::is_whitespace)
+        });
+        self.kill(start, offset);
+    }
+
+    /// Kills the alphanumeric word before the cursor.
+    pub fn kill_alphanumeric_before(&mut self) {
+        let offset = self.offset();
+        let start = word_start(&self.string[self.curr], offset, is_word);
+        self.kill(start, offset);
+    }
+
+    /// Kills the rest of the cursor's line, or the newline that ends it if there is nothing else.
+    pub fn kill_to_end(&mut self) {
+        let (offset, mut end) = (self.offset(), self.line_end());
+        if offset == end && end < self.string[self.curr].len() {
+            end += 1;
+        }
+        self.kill(offset, end);
+    }
+
+    /// Kills the cursor's line up to the cursor.
+    pub fn kill_to_start(&mut self) {
+        let (start, offset) = (self.line_start(), self.offset());
+        self.kill(start, offset);
+    }
+
+    /// Inserts the most recently killed text at the cursor.
+    pub fn yank(&mut self) {
+        if self.kill_ring.is_empty() {
+            return;
+        }
+        self.before_edit();
+
+        let index = self.kill_ring.len() - 1;
+        self.yank = Some(Yank {
+            start: self.offset(),
+            index: index,
+        });
+        self.insert_yank();
+    }
+
+    /// Replaces the text that was just yanked with the kill before it, cycling around the ring.
+    pub fn yank_pop(&mut self) {
+        let len = self.kill_ring.len();
+        match self.yank {
+            Some(ref mut yank) => yank.index = (yank.index + len - 1) % len,
+            None => return,
+        }
+        self.insert_yank();
+    }
+
+    /// Swaps the graphemes on either side of the cursor and moves past them. At the end of the
+    /// line, the last two graphemes are swapped instead.
+    pub fn transpose(&mut self) {
+        self.before_edit();
+
+        if self.cursor > 0 && self.cursor == self.len() {
+            self.cursor -= 1;
+        }
+        let offset = self.offset();
+        let end = {
+            let line = &mut self.string[self.curr];
+            let before = line[..offset].graphemes(true).next_back().map(str::to_owned);
+            let after = line[offset..].graphemes(true).next().map(str::to_owned);
+            match (before, after) {
+                (Some(before), Some(after)) => {
+                    let end = offset + after.len();
+                    line.replace_range(offset - before.len()..end, &(after + &before));
+                    end
+                }
+                _ => offset,
+            }
+        };
+        self.set_offset(end);
+        self.redraw();
+    }
+
     pub fn draw_cursor(&self) -> error::Result<()> {
-        write!(io::stdout(), "{}", cursor::Goto(self.cursor + 1, self.buf.bound().y + 1))?;
+        let bound = self.buf.bound();
+        let (row, column) = self.cursor_position();
+        let x = bound.x + self.prompt_width() + column.saturating_sub(self.scroll) as u16;
+        let y = bound.y + row.saturating_sub(self.top_row) as u16;
+        write!(
+            io::stdout(), "{}",
+            cursor::Goto(x.min(bound.right_border() - 1) + 1, y.min(bound.bottom_border() - 1) + 1)
+        )?;
         Ok(())
     }
 
     /// This should be called at the beginning of any buffer-editing functions.
     /// It deals with copying the current string into the last spot of the string buffer.
     fn before_edit(&mut self) {
+        self.end_replacements();
+        self.awaiting_confirmation = false;
         let latest = self.latest();
         if self.curr < latest {
             self.string[latest] = self.string[self.curr].clone();
@@ -121,21 +502,285 @@ impl Input {
         }
     }
 
+    /// Ends any completion, yank or search in progress, keeping the text that it inserted or found.
+    fn end_replacements(&mut self) {
+        self.completion = None;
+        self.yank = None;
+        self.search = None;
+    }
+
+    /// Removes the text between the indices, adding it to the kill ring.
+    fn kill(&mut self, start: usize, end: usize) {
+        self.before_edit();
+        if start == end {
+            return;
+        }
+
+        let killed = self.string[self.curr][start..end].to_owned();
+        self.string[self.curr].replace_range(start..end, "");
+        if self.kill_ring.len() == KILL_RING_SIZE {
+            self.kill_ring.remove(0);
+        }
+        self.kill_ring.push(killed);
+        self.set_offset(start);
+        self.redraw();
+    }
+
+    /// Puts the yanked text from the kill ring in place of the text that was yanked before it,
+    /// which always ends at the cursor.
+    fn insert_yank(&mut self) {
+        let (start, text) = match self.yank {
+            Some(ref yank) => (yank.start, self.kill_ring[yank.index].clone()),
+            None => return,
+        };
+        let offset = self.offset();
+        self.string[self.curr].replace_range(start..offset, &text);
+        self.set_offset(start + text.len());
+        self.redraw();
+    }
+
+    /// Puts the current candidate of the completion in place of the word being completed, which
+    /// always ends at the cursor.
+    fn insert_completion(&mut self) {
+        let (start, candidate) = match self.completion {
+            Some(ref completion) => {
+                (completion.start, completion.candidates[completion.current].clone())
+            }
+            None => return,
+        };
+        let offset = self.offset();
+        self.string[self.curr].replace_range(start..offset, &candidate);
+        self.set_offset(start + candidate.len());
+        self.redraw();
+    }
+
     fn bound_cursor(&mut self) {
-        if (self.cursor as usize) > self.string[self.curr].len() {
-            self.cursor = self.string[self.curr].len() as u16;
+        let len = self.len();
+        if self.cursor > len {
+            self.cursor = len;
         }
     }
 
+    /// The number of graphemes in the line being edited.
+    fn len(&self) -> usize {
+        self.string[self.curr].graphemes(true).count()
+    }
+
+    /// The byte index of the cursor in the line being edited.
+    fn offset(&self) -> usize {
+        self.string[self.curr].graphemes(true).take(self.cursor).map(str::len).sum()
+    }
+
+    /// Moves the cursor to the grapheme that starts at the byte index, or just past the grapheme
+    /// that contains it.
+    fn set_offset(&mut self, offset: usize) {
+        self.cursor = self.string[self.curr][..offset].graphemes(true).count();
+    }
+
     fn latest(&self) -> usize {
         self.string.len() - 1
     }
 
+    /// Drops the oldest sent lines until the history is no longer than its size.
+    fn trim_history(&mut self) {
+        let excess = self.latest().saturating_sub(self.history_size);
+        self.string.drain(..excess);
+    }
+
+    /// Shows the latest line, at or before the given one, that contains the query of the search.
+    fn search_from(&mut self, from: usize) {
+        let query = match self.search {
+            Some(ref search) => search.query.clone(),
+            None => return,
+        };
+        let found = {
+            let lines = &self.string;
+            lines[..from + 1].iter().rposition(|line| line.contains(&query[..])).map(|curr| {
+                let offset = lines[curr].find(&query[..]).unwrap_or(0);
+                (curr, lines[curr][..offset].graphemes(true).count())
+            })
+        };
+        if let Some(ref mut search) = self.search {
+            search.failed = found.is_none();
+        }
+        if let Some((curr, cursor)) = found {
+            self.curr = curr;
+            self.cursor = cursor;
+        }
+        self.redraw();
+    }
+
+    /// The prompt shown before the line, which shows the query while searching.
+    fn current_prompt(&self) -> Cow<str> {
+        match self.search {
+            Some(ref search) => {
+                let status = if search.failed { "failed search" } else { "search" };
+                Cow::Owned(format!("({}) `{}': ", status, search.query))
+            }
+            None => Cow::Borrowed(&self.prompt),
+        }
+    }
+
+    /// The line that the cursor is on, and its display column within that line.
+    fn cursor_position(&self) -> (usize, usize) {
+        let before = &self.string[self.curr][..self.offset()];
+        let start = before.rfind('\n').map_or(0, |i| i + 1);
+        (before.matches('\n').count(), before[start..].width())
+    }
+
+    /// The byte index of the start of the cursor's line.
+    fn line_start(&self) -> usize {
+        self.string[self.curr][..self.offset()].rfind('\n').map_or(0, |i| i + 1)
+    }
+
+    /// The byte index of the end of the cursor's line, before the newline that ends it.
+    fn line_end(&self) -> usize {
+        let offset = self.offset();
+        let line = &self.string[self.curr];
+        line[offset..].find('\n').map_or(line.len(), |i| offset + i)
+    }
+
+    /// Moves the cursor to the grapheme at or just before the display column of the line.
+    fn move_to(&mut self, row: usize, column: usize) {
+        let offset = {
+            let line = &self.string[self.curr];
+            let start = line.split('\n').take(row).map(|l| l.len() + 1).sum::<usize>();
+            let mut offset = start;
+            let mut width = 0;
+            for g in line[start..].split('\n').next().unwrap_or("").graphemes(true) {
+                width += g.width();
+                if width > column {
+                    break;
+                }
+                offset += g.len();
+            }
+            offset
+        };
+        self.set_offset(offset);
+        self.redraw();
+    }
+
+    /// The number of columns taken by the prompt, which is cut short rather than leave less than
+    /// half of the line for editing.
+    fn prompt_width(&self) -> u16 {
+        (self.current_prompt().width() as u16).min(self.buf.width() / 2)
+    }
+
+    /// Scrolls the lines so that the cursor is visible, and not covered by the indicators of
+    /// text hidden past either edge.
+    fn scroll_to_cursor(&mut self) {
+        let (row, column) = self.cursor_position();
+        let height = self.buf.height() as usize;
+        if row < self.top_row {
+            self.top_row = row;
+        } else if row >= self.top_row + height {
+            self.top_row = row + 1 - height;
+        }
+        // Lines are not left empty at the bottom while there are lines hidden at the top.
+        self.top_row = self.top_row.min(self.line_count().saturating_sub(height));
+
+        let width = (self.buf.width() - self.prompt_width()) as usize;
+        let line_width = self.string[self.curr].split('\n').nth(row).map_or(0, |l| l.width());
+        if line_width < width {
+            self.scroll = 0;
+        } else if column < self.scroll + 1 {
+            self.scroll = column.saturating_sub(1);
+        } else if column + 2 > self.scroll + width {
+            self.scroll = column + 2 - width;
+        }
+    }
+
     fn redraw(&mut self) {
+        self.scroll_to_cursor();
         self.buf.reset();
-        let y = self.buf.bound().y;
-        self.buf.set_str(0, y, &self.string[self.curr]);
+
+        let bound = *self.buf.bound();
+        let prompt_width = self.prompt_width();
+        let mut x = bound.x;
+        let prompt = self.current_prompt().into_owned();
+        for g in prompt.graphemes(true) {
+            let width = g.width() as u16;
+            if x + width > bound.x + prompt_width {
+                break;
+            }
+            x = set_grapheme(&mut self.buf, x, bound.y, g);
+        }
+
+        // Every line is scrolled along with the cursor's, and only the graphemes that fit whole
+        // between the indicators are shown.
+        let x = bound.x + prompt_width;
+        let width = (bound.width - prompt_width) as usize;
+        let lines = self.string[self.curr].split('\n').skip(self.top_row);
+        for (y, line) in (bound.y..bound.bottom_border()).zip(lines) {
+            let hidden_left = self.scroll > 0 && !line.is_empty();
+            let hidden_right = line.width() > self.scroll + width;
+            let start = self.scroll + hidden_left as usize;
+            let end = self.scroll + width - hidden_right as usize;
+
+            let mut column = 0;
+            for g in line.graphemes(true) {
+                let width = g.width();
+                if column >= start && column + width <= end {
+                    set_grapheme(&mut self.buf, x + (column - self.scroll) as u16, y, g);
+                }
+                column += width;
+            }
+
+            if hidden_left {
+                self.buf.set(x, y, "<");
+            }
+            if hidden_right {
+                self.buf.set(bound.right_border() - 1, y, ">");
+            }
+        }
+    }
+}
+
+/// Sets the cell at (x, y) to the grapheme, returning the position after it. The other cells
+/// covered by a wide grapheme are left empty, so that the terminal does not draw over its second
+/// half.
+fn set_grapheme(buf: &mut Buffer, x: u16, y: u16, g: &str) -> u16 {
+    let width = g.width() as u16;
+    buf.set(x, y, g);
+    for i in 1..width {
+        buf.set(x + i, y, "");
+    }
+    x + width
+}
+
+/// Whether the grapheme is part of a word for the purposes of word motions.
+fn is_word(g: &str) -> bool {
+    g.chars().next().is_some_and(char::is_alphanumeric)
+}
+
+/// The byte index of the start of the word that ends before the index, skipping anything between.
+fn word_start<F: Fn(&str) -> bool>(line: &str, idx: usize, in_word: F) -> usize {
+    let mut start = idx;
+    let mut seen_word = false;
+    for (i, g) in line[..idx].grapheme_indices(true).rev() {
+        if in_word(g) {
+            seen_word = true;
+        } else if seen_word {
+            break;
+        }
+        start = i;
+    }
+    start
+}
+
+/// The byte index of the end of the word that starts after the index, skipping anything between.
+fn word_end<F: Fn(&str) -> bool>(line: &str, idx: usize, in_word: F) -> usize {
+    let mut end = idx;
+    let mut seen_word = false;
+    for (i, g) in line[idx..].grapheme_indices(true) {
+        if in_word(g) {
+            seen_word = true;
+        } else if seen_word {
+            break;
+        }
+        end = idx + i + g.len();
     }
+    end
 }
 
 impl Widget for Input {
@@ -143,3 +788,153 @@ impl Widget for Input {
         buffer.merge(&self.buf);
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::Input;
+
+    /// An input of the given width at the top left, with the text typed into it.
+    fn typed(width: u16, text: &str) -> Input {
+        let mut input = Input::new(0, 0, width);
+        for c in text.chars() {
+            input.add_char(c);
+        }
+        input
+    }
+
+    /// The cells of the input's first row, with those covered by wide graphemes left out.
+    fn row(input: &Input) -> String {
+        let bound = *input.buf.bound();
+        (bound.x..bound.right_border()).map(|x| input.buf.get(x, bound.y)).collect()
+    }
+
+    #[test]
+    fn cursor_moves_by_grapheme() {
+        let mut input = typed(20, "e\u{301}x日");
+        assert_eq!(input.cursor, 3);
+        input.move_left();
+        input.move_left();
+        assert_eq!(input.cursor, 1);
+        input.add_char('a');
+        assert_eq!(input.get_content(), "e\u{301}ax日");
+        input.backspace();
+        input.backspace();
+        assert_eq!(input.get_content(), "x日");
+        assert_eq!(input.cursor, 0);
+        input.delete();
+        assert_eq!(input.get_content(), "日");
+        input.move_end();
+        input.delete();
+        input.move_right();
+        assert_eq!((input.get_content(), input.cursor), ("日", 1));
+    }
+
+    #[test]
+    fn combining_characters_join_the_grapheme_before() {
+        let mut input = typed(20, "ae");
+        input.move_left();
+        input.add_char('\u{301}');
+        assert_eq!((input.get_content(), input.cursor), ("a\u{301}e", 1));
+        input.backspace();
+        assert_eq!((input.get_content(), input.cursor), ("e", 0));
+    }
+
+    #[test]
+    fn word_motions() {
+        let mut input = typed(40, "héllo, wörld  日本");
+        input.move_word_left();
+        assert_eq!(input.cursor, 14);
+        input.move_word_left();
+        assert_eq!(input.cursor, 7);
+        input.move_word_left();
+        input.move_word_left();
+        assert_eq!(input.cursor, 0);
+        input.move_word_right();
+        assert_eq!(input.cursor, 5);
+        input.move_word_right();
+        assert_eq!(input.cursor, 12);
+        input.move_word_right();
+        input.move_word_right();
+        assert_eq!(input.cursor, 16);
+    }
+
+    #[test]
+    fn transpose_graphemes() {
+        let mut input = typed(20, "ae\u{301}日");
+        input.transpose();
+        assert_eq!((input.get_content(), input.cursor), ("a日e\u{301}", 3));
+        input.move_home();
+        input.move_right();
+        input.transpose();
+        assert_eq!((input.get_content(), input.cursor), ("日ae\u{301}", 2));
+        input.move_home();
+        input.transpose();
+        assert_eq!((input.get_content(), input.cursor), ("日ae\u{301}", 0));
+    }
+
+    #[test]
+    fn kill_and_yank() {
+        let mut input = typed(40, "one 日本 twö");
+        input.kill_word_before();
+        assert_eq!(input.get_content(), "one 日本 ");
+        input.kill_word_before();
+        assert_eq!((input.get_content(), input.cursor), ("one ", 4));
+        input.yank();
+        assert_eq!(input.get_content(), "one 日本 ");
+        input.yank_pop();
+        assert_eq!((input.get_content(), input.cursor), ("one twö", 7));
+        input.move_word_left();
+        input.kill_to_end();
+        assert_eq!(input.get_content(), "one ");
+        input.kill_to_start();
+        assert_eq!((input.get_content(), input.cursor), ("", 0));
+        input.yank();
+        assert_eq!(input.get_content(), "one ");
+    }
+
+    #[test]
+    fn scroll_wide_graphemes() {
+        // The second half of a wide grapheme is left empty, and those that do not fit whole
+        // between the indicators are not drawn.
+        let mut input = typed(5, "ab日本");
+        assert_eq!(row(&input), "<本  ");
+        input.move_home();
+        assert_eq!(row(&input), "ab日>");
+        input.move_right();
+        input.move_right();
+        assert_eq!(row(&input), "ab日>");
+        input.move_right();
+        assert_eq!(row(&input), "<日本");
+        input.move_right();
+        assert_eq!(row(&input), "<本  ");
+    }
+
+    #[test]
+    fn scroll_combining_characters() {
+        let mut input = typed(4, "abce\u{301}");
+        input.move_left();
+        assert_eq!(row(&input), "<e\u{301}  ");
+        input.backspace();
+        assert_eq!(row(&input), "abe\u{301} ");
+    }
+
+    #[test]
+    fn scroll_past_the_prompt() {
+        let mut input = typed(8, "abcdef");
+        input.set_prompt("[#c] ");
+        // The prompt is cut short to leave half of the line for editing.
+        assert_eq!(row(&input), "[#c]<f  ");
+        input.move_home();
+        assert_eq!(row(&input), "[#c]abc>");
+    }
+
+    #[test]
+    fn history_is_trimmed_to_its_size() {
+        let mut input = typed(20, "draft");
+        input.set_history(vec!["a".to_owned(), "b".to_owned(), "c".to_owned()]);
+        input.set_history_size(2);
+        assert_eq!(input.string, vec!["b", "c", "draft"]);
+        input.reset();
+        assert_eq!(input.string, vec!["c", "draft", ""]);
+    }
+}
@@ -2,11 +2,15 @@ use view::{Buffer};
 
 mod chatbuf;
 mod input;
+mod nicklist;
 mod tabline;
+mod topicbar;
 
 pub use self::chatbuf::ChatBuf;
 pub use self::input::Input;
+pub use self::nicklist::NickList;
 pub use self::tabline::TabLine;
+pub use self::topicbar::TopicBar;
 
 pub trait Widget {
     fn draw(&self, buffer: &mut Buffer);
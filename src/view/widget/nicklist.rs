@@ -0,0 +1,80 @@
+use unicode_width::UnicodeWidthStr;
+
+use model::MemberList;
+use utils;
+use view::{Bound, Buffer, Color, Style, Widget};
+
+pub struct NickList {
+    buf: Buffer,
+    visible: bool,
+}
+
+impl NickList {
+    pub fn new(bound: Bound) -> NickList {
+        NickList {
+            buf: Buffer::empty(bound),
+            visible: true,
+        }
+    }
+
+    pub fn is_visible(&self) -> bool {
+        self.visible
+    }
+
+    pub fn toggle(&mut self) {
+        self.visible = !self.visible;
+    }
+
+    pub fn resize(&mut self, bound: Bound) {
+        self.buf = Buffer::empty(bound);
+    }
+
+    /// Redraws the list from the given members, or as an empty pane if there are none.
+    pub fn redraw(&mut self, members: Option<&MemberList>) {
+        self.buf.reset();
+
+        let bound = *self.buf.bound();
+        if bound.width == 0 {
+            return;
+        }
+
+        // Draw the separator between the chat area and the list.
+        let sep_style = Style::from(Color::LightBlack);
+        for y in bound.top_border()..bound.bottom_border() {
+            self.buf.set_str_styled(bound.x, y, "\u{2502}", sep_style);
+        }
+
+        let members = match members {
+            Some(members) => members,
+            None => return,
+        };
+
+        let width = bound.width as usize - 1;
+        let height = bound.height as usize;
+        let hidden = if members.len() > height { members.len() - height + 1 } else { 0 };
+        for (y, member) in (bound.top_border()..bound.bottom_border()).zip(members.iter()) {
+            if hidden > 0 && y == bound.bottom_border() - 1 {
+                let more = utils::truncate(&format!("({} more)", hidden), width);
+                self.buf.set_str_styled(bound.x + 1, y, &more, sep_style);
+                break;
+            }
+
+            let prefix = utils::truncate(member.prefix(), width);
+            let nick = utils::truncate(member.nickname(), width - prefix.width());
+            self.buf.set_str_styled(bound.x + 1, y, &prefix, Style::from(Color::Yellow));
+            self.buf.set_str_styled(
+                bound.x + 1 + prefix.width() as u16, y, &nick,
+                Style::from(utils::colorize(member.nickname()))
+            );
+        }
+    }
+}
+
+impl Widget for NickList {
+    fn draw(&self, buffer: &mut Buffer) {
+        if self.visible {
+            buffer.merge(&self.buf);
+        }
+    }
+}
+
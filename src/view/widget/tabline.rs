@@ -1,5 +1,12 @@
+use std::cmp;
+
+use unicode_width::UnicodeWidthStr;
+
+use config::DEFAULT_TAB_NAME_WIDTH;
 use error;
-use view::{Bound, Buffer, Color, Style, Widget};
+use model::{Activity, ActivityLevel, BufferId};
+use utils;
+use view::{Bound, Buffer, Modifier, Style, Theme, Widget};
 
 const EXTRA_SIZE: u16 = 3;
 
@@ -7,7 +14,13 @@ pub struct TabLine {
     buf: Buffer,
     tabs: Vec<Tab>,
     curr: usize,
-    cursor: u16,
+    /// The index of the first tab shown, when there are too many tabs to show them all.
+    offset: usize,
+    /// The width at which the names of tabs are cut off.
+    name_width: usize,
+    /// Whether each tab is prefixed with its number.
+    numbered: bool,
+    theme: Theme,
 }
 
 impl TabLine {
@@ -16,7 +29,10 @@ impl TabLine {
             buf: Buffer::empty(Bound::new(x, y, width, 1)),
             tabs: Vec::new(),
             curr: 0,
-            cursor: 0,
+            offset: 0,
+            name_width: DEFAULT_TAB_NAME_WIDTH,
+            numbered: false,
+            theme: Theme::default(),
         }
     }
 
@@ -24,97 +40,146 @@ impl TabLine {
         TabLine::new(buf.bound().x, buf.bound().y + buf.bound().height, buf.bound().width)
     }
 
-    pub fn add_tab(&mut self, content: &str, current: bool) {
-        let mut tab = Tab::new(self.cursor, self.buf.bound().y, content);
+    pub fn set_theme(&mut self, theme: Theme) {
+        self.theme = theme;
+        self.redraw();
+    }
+
+    /// Sets the width at which the names of tabs are cut off.
+    pub fn set_name_width(&mut self, width: usize) {
+        self.name_width = width;
+        self.redraw();
+    }
+
+    /// Sets whether each tab is prefixed with its number.
+    pub fn set_numbered(&mut self, numbered: bool) {
+        self.numbered = numbered;
+        self.redraw();
+    }
+
+    /// Moves the tab line to the given position and width, redrawing all of the tabs.
+    pub fn resize(&mut self, x: u16, y: u16, width: u16) {
+        self.buf.resize(Bound::new(x, y, width, 1));
+        for tab in &mut self.tabs {
+            tab.buf.move_y(y);
+        }
+        self.redraw();
+    }
+
+    /// Adds a tab for the buffer at the end of its network's group of tabs. The status buffer
+    /// of a network heads its group, and is titled with the network's name.
+    pub fn add_tab(&mut self, id: &BufferId, current: bool) {
+        let idx = self.tabs.iter().rposition(|tab| tab.id.server == id.server)
+            .map_or(self.tabs.len(), |i| i + 1);
         if current {
-            self.curr = self.tabs.len();
-            tab.highlighted = true;
-            self.highlight_precursor();
-        }
-        tab.redraw();
-        tab.draw(&mut self.buf);
-        self.tabs.push(tab);
-        self.cursor += content.len() as u16 + EXTRA_SIZE;
-    }
-
-    /// Removes the tab with the specified title.
-    ///
-    /// Note: bugs may occur if this is the current tab. So, switch first! (Until this is fixed...)
-    pub fn remove_tab(&mut self, content: &str) -> error::Result<()> {
-        let idx = self.tabs.iter().enumerate().find(|&(_, tab)| {
-            tab.content == content
-        }).map(|(i, _)| i);
+            self.curr = idx;
+        } else if idx <= self.curr && !self.tabs.is_empty() {
+            self.curr += 1;
+        }
+        self.tabs.insert(idx, Tab::new(0, self.buf.bound().y, id));
+        self.redraw();
+    }
+
+    /// Removes the tab for the specified buffer. If it was the current tab, the tab after it, or
+    /// else the last tab, becomes current.
+    pub fn remove_tab(&mut self, id: &BufferId) -> error::Result<()> {
+        let idx = self.tabs.iter().position(|tab| &tab.id == id);
         match idx {
             Some(idx) => {
                 if idx < self.curr {
                     self.curr -= 1;
                 }
                 self.tabs.remove(idx);
+                self.curr = cmp::min(self.curr, self.tabs.len().saturating_sub(1));
                 self.redraw();
                 Ok(())
             }
-            None => Err(error::Error::TabNotFound { tab: content.to_owned() }),
-        }
-    }
-
-    /// Switches to the specified tab based on its title.
-    pub fn switch_to(&mut self, content: &str) -> error::Result<()> {
-        let original = self.curr;
-        {
-            match self.tabs.iter_mut().enumerate().find(|&(_, ref tab)| {
-                tab.content == content
-            }) {
-                Some((i, tab)) => {
-                    self.curr = i;
-                    tab.highlighted = true;
-                    tab.redraw();
-                    tab.draw(&mut self.buf);
-                }
-                None => return Err(error::Error::TabNotFound { tab: content.to_owned() }),
-            }
+            None => Err(error::Error::TabNotFound { tab: id.to_string() }),
         }
-        if original != 0 {
-            self.tabs[original - 1].before_highlighted = false;
-            self.tabs[original - 1].redraw();
-            self.tabs[original - 1].draw(&mut self.buf);
+    }
+
+    /// Switches to the tab for the specified buffer, scrolling the tab line to show it.
+    pub fn switch_to(&mut self, id: &BufferId) -> error::Result<()> {
+        match self.tabs.iter().position(|tab| &tab.id == id) {
+            Some(idx) => {
+                self.curr = idx;
+                self.redraw();
+                Ok(())
+            }
+            None => Err(error::Error::TabNotFound { tab: id.to_string() }),
         }
-        if original != self.curr {
-            self.tabs[original].highlighted = false;
-            self.tabs[original].redraw();
-            self.tabs[original].draw(&mut self.buf);
+    }
+
+    /// Shows the activity in the buffer since it was last viewed on its tab.
+    pub fn set_activity(&mut self, id: &BufferId, activity: Activity) -> error::Result<()> {
+        let tab = self.tabs.iter_mut().find(|tab| &tab.id == id).ok_or_else(|| {
+            error::Error::TabNotFound { tab: id.to_string() }
+        })?;
+        if tab.activity != activity {
+            tab.set_activity(activity);
+            self.redraw();
         }
-        self.highlight_precursor();
         Ok(())
     }
 
+    /// Redraws the tabs that fit, scrolled to show the current one. Tabs hidden off either end
+    /// are marked by `<` and `>`.
     pub fn redraw(&mut self) {
         self.buf.reset();
-        self.cursor = 0;
         for (n, tab) in self.tabs.iter_mut().enumerate() {
-            if n == self.curr {
-                tab.before_highlighted = false;
-                tab.highlighted = true;
-            } else if self.curr != 0 && n == self.curr - 1 {
-                tab.before_highlighted = true;
-                tab.highlighted = false;
-            } else {
-                tab.before_highlighted = false;
-                tab.highlighted = false;
+            let number = if self.numbered { Some(n + 1) } else { None };
+            tab.set_label(number, self.name_width);
+        }
+        self.scroll_to_current();
+
+        let (x, y, width) = (self.buf.bound().x, self.buf.bound().y, self.buf.width());
+        let mut cursor = if self.offset > 0 { 1 } else { 0 };
+        let mut end = self.offset;
+        let len = self.tabs.len();
+        for (n, tab) in self.tabs.iter_mut().enumerate().skip(self.offset) {
+            // Room is left for the `>` marker unless this is the last tab.
+            let room = if n + 1 < len { width.saturating_sub(1) } else { width };
+            if cursor + tab.width() > room {
+                break;
             }
-            tab.buf.move_x(self.cursor);
-            tab.redraw();
+
+            tab.highlighted = n == self.curr;
+            tab.before_highlighted = n + 1 == self.curr;
+            tab.buf.move_x(x + cursor);
+            tab.redraw(&self.theme);
             tab.draw(&mut self.buf);
-            self.cursor += tab.content.len() as u16 + EXTRA_SIZE;
+            cursor += tab.width();
+            end = n + 1;
+        }
+
+        let style = Style::default().fg(self.theme.bar_fg).bg(self.theme.bar_bg);
+        if self.offset > 0 {
+            self.buf.set_str_styled(x, y, "<", style);
+        }
+        if end < len && width > 0 {
+            self.buf.set_str_styled(x + width - 1, y, ">", style);
         }
     }
 
-    fn highlight_precursor(&mut self) {
-        if self.curr != 0 {
-            let mut tab = &mut self.tabs[self.curr - 1];
-            tab.before_highlighted = true;
-            tab.redraw();
-            tab.draw(&mut self.buf);
+    /// Scrolls the tab line so that the current tab is shown, along with as many of the tabs
+    /// before it as fit.
+    fn scroll_to_current(&mut self) {
+        self.offset = cmp::min(self.offset, self.curr);
+        while self.offset < self.curr && !self.fits(self.offset, self.curr) {
+            self.offset += 1;
         }
+        while self.offset > 0 && self.fits(self.offset - 1, self.curr) {
+            self.offset -= 1;
+        }
+    }
+
+    /// Whether the tabs from `first` to `last` fit in the tab line together, along with the
+    /// markers for any tabs hidden off either end.
+    fn fits(&self, first: usize, last: usize) -> bool {
+        let left = if first > 0 { 1 } else { 0 };
+        let right = if last + 1 < self.tabs.len() { 1 } else { 0 };
+        let tabs = self.tabs[first..last + 1].iter().fold(0, |width, tab| width + tab.width());
+        left + tabs + right <= self.buf.width()
     }
 }
 
@@ -126,54 +191,105 @@ impl Widget for TabLine {
 
 struct Tab {
     buf: Buffer,
+    id: BufferId,
     content: String,
+    /// The text shown on the tab, i.e. its number, name and unread count.
+    label: String,
+    activity: Activity,
     highlighted: bool,
     before_highlighted: bool,
 }
 
 impl Tab {
-    pub fn new(x: u16, y: u16, content: &str) -> Tab {
+    pub fn new(x: u16, y: u16, id: &BufferId) -> Tab {
+        let content = if id.is_status() { &id.server } else { &id.name };
         Tab {
-            buf: Buffer::empty(Bound::new(x, y, content.len() as u16 + EXTRA_SIZE, 1)),
+            buf: Buffer::empty(Bound::new(x, y, content.width() as u16 + EXTRA_SIZE, 1)),
+            id: id.clone(),
             content: content.to_owned(),
+            label: content.to_owned(),
+            activity: Activity::default(),
             highlighted: false,
             before_highlighted: false,
         }
     }
 
-    pub fn style(&self) -> Style {
-        if self.highlighted {
+    pub fn set_activity(&mut self, activity: Activity) {
+        self.activity = activity;
+    }
+
+    /// Sets the text shown on the tab to its number, if any, and its name, cut off at the
+    /// specified width, followed by the number of unread messages if there are any. The tab is
+    /// resized to fit.
+    pub fn set_label(&mut self, number: Option<usize>, name_width: usize) {
+        let mut label = number.map_or_else(String::new, |number| format!("{}:", number));
+        if self.content.width() > name_width {
+            label.push_str(&utils::truncate(&self.content, name_width.saturating_sub(1)));
+            label.push('…');
+        } else {
+            label.push_str(&self.content);
+        }
+        if self.activity.unread > 0 {
+            label.push_str(&format!(" ({})", self.activity.unread));
+        }
+
+        self.label = label;
+        let bound = *self.buf.bound();
+        self.buf.resize(Bound::new(bound.x, bound.y, self.width(), 1));
+    }
+
+    /// The width of the tab, including the padding around its label and its separator.
+    pub fn width(&self) -> u16 {
+        self.label.width() as u16 + EXTRA_SIZE
+    }
+
+    pub fn style(&self, theme: &Theme) -> Style {
+        let style = if self.highlighted {
             Style {
-                fg: Color::Black,
-                bg: Color::Magenta,
+                fg: theme.active_fg,
+                bg: theme.active_bg,
                 ..Style::default()
             }
         } else {
+            let fg = match self.activity.level {
+                ActivityLevel::None => theme.bar_fg,
+                ActivityLevel::Noise => theme.activity_noise,
+                ActivityLevel::Message => theme.activity_message,
+                ActivityLevel::Highlight => theme.activity_highlight,
+            };
             Style {
-                fg: Color::LightWhite,
-                bg: Color::Black,
+                fg: fg,
+                bg: theme.bar_bg,
                 ..Style::default()
             }
+        };
+
+        // Network names stand out from the buffers grouped under them, as do highlights.
+        let highlight = !self.highlighted && self.activity.level == ActivityLevel::Highlight;
+        if self.id.is_status() || highlight {
+            style.modifier(Modifier::Bold)
+        } else {
+            style
         }
     }
 
-    pub fn sep_style(&self) -> Style {
+    pub fn sep_style(&self, theme: &Theme) -> Style {
         if self.highlighted {
             Style {
-                fg: Color::Magenta,
-                bg: Color::Black,
+                fg: theme.active_bg,
+                bg: theme.bar_bg,
                 ..Style::default()
             }
         } else if self.before_highlighted {
             Style {
-                fg: Color::Black,
-                bg: Color::Magenta,
+                fg: theme.bar_bg,
+                bg: theme.active_bg,
                 ..Style::default()
             }
         } else {
             Style {
-                fg: Color::LightWhite,
-                bg: Color::Black,
+                fg: theme.bar_fg,
+                bg: theme.bar_bg,
                 ..Style::default()
             }
         }
@@ -187,15 +303,17 @@ impl Tab {
         }
     }
 
-    pub fn redraw(&mut self) {
+    pub fn redraw(&mut self, theme: &Theme) {
         let sep = self.sep();
         let (x, y) = (self.buf.bound().x, self.buf.bound().y);
-        let (style, sep_style) = (self.style(), self.sep_style());
+        let (style, sep_style) = (self.style(theme), self.sep_style(theme));
+
+        let width = self.label.width() as u16;
 
         self.buf.set_str_styled(x, y, " ", style);
-        self.buf.set_str_styled(x + 1, y, &self.content, style);
-        self.buf.set_str_styled(x + 1 + self.content.len() as u16, y, " ", style);
-        self.buf.set_str_styled(x + 1 + self.content.len() as u16 + 1, y, sep, sep_style);
+        self.buf.set_str_styled(x + 1, y, &self.label, style);
+        self.buf.set_str_styled(x + 1 + width, y, " ", style);
+        self.buf.set_str_styled(x + 1 + width + 1, y, sep, sep_style);
     }
 }
 
@@ -204,3 +322,85 @@ impl Widget for Tab {
         buffer.merge(&self.buf);
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use model::{Activity, ActivityLevel, BufferId};
+    use super::TabLine;
+
+    /// A tab line of the given width, with a tab for the network followed by one for each of
+    /// the channels.
+    fn tabline(width: u16, channels: &[&str]) -> TabLine {
+        let mut tabline = TabLine::new(0, 0, width);
+        tabline.add_tab(&BufferId::status("net"), true);
+        for chan in channels {
+            tabline.add_tab(&BufferId::new("net", chan), false);
+        }
+        tabline
+    }
+
+    /// The cells of the tab line, with the separators between tabs shown as `|`.
+    fn row(tabline: &TabLine) -> String {
+        let bound = *tabline.buf.bound();
+        (bound.x..bound.right_border()).map(|x| match tabline.buf.get(x, bound.y) {
+            "\u{e0b0}" | "\u{e0b1}" => "|",
+            cell => cell,
+        }).collect()
+    }
+
+    fn switch_to(tabline: &mut TabLine, name: &str) {
+        tabline.switch_to(&BufferId::new("net", name)).unwrap();
+    }
+
+    #[test]
+    fn tabs_that_fit_exactly() {
+        let tabline = tabline(17, &["#a", "#bb"]);
+        assert_eq!(row(&tabline), " net | #a | #bb |");
+    }
+
+    #[test]
+    fn scroll_to_the_current_tab() {
+        let mut tabline = tabline(16, &["#a", "#bb"]);
+        assert_eq!(row(&tabline), " net | #a |    >");
+        switch_to(&mut tabline, "#bb");
+        assert_eq!((tabline.offset, &row(&tabline)[..]), (1, "< #a | #bb |    "));
+        // As many of the tabs before the current one are shown as fit.
+        switch_to(&mut tabline, "#a");
+        assert_eq!((tabline.offset, &row(&tabline)[..]), (0, " net | #a |    >"));
+    }
+
+    #[test]
+    fn markers_on_both_ends() {
+        let mut tabline = tabline(16, &["#a", "#bb", "#c"]);
+        switch_to(&mut tabline, "#bb");
+        assert_eq!(row(&tabline), "< #a | #bb |   >");
+        switch_to(&mut tabline, "#c");
+        assert_eq!(row(&tabline), "< #bb | #c |    ");
+    }
+
+    #[test]
+    fn remove_the_last_tab() {
+        let mut tabline = tabline(16, &["#a", "#bb"]);
+        switch_to(&mut tabline, "#bb");
+        tabline.remove_tab(&BufferId::new("net", "#bb")).unwrap();
+        assert_eq!((tabline.curr, tabline.offset), (1, 0));
+        assert_eq!(row(&tabline), " net | #a |     ");
+        assert!(tabline.remove_tab(&BufferId::new("net", "#bb")).is_err());
+    }
+
+    #[test]
+    fn truncated_and_numbered_labels() {
+        let mut tabline = tabline(40, &["#rust", "#c"]);
+        tabline.set_name_width(3);
+        assert_eq!(row(&tabline).trim_end(), " net | #r… | #c |");
+        tabline.set_numbered(true);
+        assert_eq!(row(&tabline).trim_end(), " 1:net | 2:#r… | 3:#c |");
+
+        let mut activity = Activity::default();
+        activity.add(ActivityLevel::Noise);
+        activity.add(ActivityLevel::Message);
+        activity.add(ActivityLevel::Highlight);
+        tabline.set_activity(&BufferId::new("net", "#c"), activity).unwrap();
+        assert_eq!(row(&tabline).trim_end(), " 1:net | 2:#r… | 3:#c (2) |");
+    }
+}
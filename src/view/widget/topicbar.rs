@@ -0,0 +1,58 @@
+use unicode_width::UnicodeWidthStr;
+
+use model::Topic;
+use utils;
+use view::{Bound, Buffer, Style, Theme, Widget};
+
+pub struct TopicBar {
+    buf: Buffer,
+    theme: Theme,
+}
+
+impl TopicBar {
+    pub fn new(x: u16, y: u16, width: u16) -> TopicBar {
+        TopicBar {
+            buf: Buffer::empty(Bound::new(x, y, width, 1)),
+            theme: Theme::default(),
+        }
+    }
+
+    pub fn set_theme(&mut self, theme: Theme) {
+        self.theme = theme;
+    }
+
+    pub fn resize(&mut self, x: u16, y: u16, width: u16) {
+        self.buf = Buffer::empty(Bound::new(x, y, width, 1));
+    }
+
+    /// Redraws the bar with the given topic, stripped of formatting, and who set it if known. The
+    /// topic is truncated first, so that its setter stays in view.
+    pub fn redraw(&mut self, topic: Option<&Topic>) {
+        let (x, y) = (self.buf.bound().x, self.buf.bound().y);
+        let width = self.buf.width() as usize;
+        let style = Style {
+            fg: self.theme.bar_fg,
+            bg: self.theme.bar_bg,
+            ..Style::default()
+        };
+
+        let text = topic.map(|topic| utils::strip_formatting(&topic.text)).unwrap_or_default();
+        let set_by = match topic.and_then(|topic| topic.set_by.as_ref()) {
+            Some(setter) if !text.is_empty() => format!(" (set by {})", setter),
+            _ => String::new(),
+        };
+        let topic = if set_by.width() < width {
+            utils::truncate(&text, width - set_by.width()) + &set_by
+        } else {
+            utils::truncate(&(text + &set_by), width)
+        };
+        let padding = " ".repeat(width - topic.width());
+        self.buf.set_str_styled(x, y, &(topic + &padding), style);
+    }
+}
+
+impl Widget for TopicBar {
+    fn draw(&self, buffer: &mut Buffer) {
+        buffer.merge(&self.buf);
+    }
+}